@@ -14,6 +14,7 @@ use cosmodrome::{
         PassportType,
     },
     passport_register::MemoryPassportRegister,
+    preflight::Preflight,
     storage::{
         Storage,
     },
@@ -64,18 +65,22 @@ async fn private(user: BoardingPass<JsonWebToken, Bearer>) -> String {
 
 #[launch]
 fn simple_login() -> _ {
-    // We need to have a global register where all users are stored.
-    let register = MemoryPassportRegister::from(vec![Passport::new(
+    let mut passport = Passport::new(
         "simple_user",
         "somepassword",
         &["simple_service"],
         PassportType::Admin,
     )
-    .unwrap()]);
+    .unwrap();
+    // Unconfirmed passports are rejected at login.
+    passport.confirmed = true;
+    // We need to have a global register where all users are stored.
+    let register = MemoryPassportRegister::from(vec![passport]);
     let cipher = JwtCipher::random();
 
     rocket::build()
         .mount("/", routes![index, private, login])
         .manage(register)
         .manage(cipher)
+        .attach(Preflight::<MemoryPassportRegister>::new())
 }
@@ -0,0 +1,23 @@
+-- Passports of the SqlitePassportRegister. The statements are kept
+-- compatible with Postgres.
+
+CREATE TABLE passport_types (
+    name TEXT PRIMARY KEY
+);
+
+INSERT INTO passport_types (name) VALUES ('Admin'), ('Moderator'), ('User');
+
+CREATE TABLE passports (
+    id TEXT PRIMARY KEY,
+    password TEXT NOT NULL,
+    account_type TEXT NOT NULL REFERENCES passport_types (name),
+    disabled BOOLEAN NOT NULL DEFAULT FALSE,
+    confirmed BOOLEAN NOT NULL DEFAULT FALSE,
+    expires_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE TABLE passport_services (
+    passport_id TEXT NOT NULL REFERENCES passports (id) ON DELETE CASCADE,
+    service TEXT NOT NULL,
+    PRIMARY KEY (passport_id, service)
+);
@@ -1,7 +1,28 @@
+pub mod api_key;
+pub mod asynchronous;
 pub mod auth_type;
+pub mod authorization;
 pub mod boarding_pass;
+pub mod challenge;
 pub mod ciphering;
+pub mod config;
+pub mod error;
 pub mod gate;
+pub mod jwks;
 pub mod passport;
 pub mod passport_register;
+pub mod policy;
+pub mod preflight;
+pub mod problem;
+pub mod refresh;
+pub mod revocation;
+pub mod routes;
+pub mod session;
+#[cfg(feature = "sqlite")]
+#[doc(cfg(feature = "sqlite"))]
+pub mod sql;
 pub mod storage;
+pub mod traveler;
+
+pub use error::Error;
+pub use routes::routes;
@@ -0,0 +1,351 @@
+//! API keys for machine clients, used by the [ApiKey](super::auth_type::ApiKey)
+//! [AuthType](super::auth_type::AuthType).
+//!
+//! A key has the form `<prefix>_<id>_<secret>`. The prefix makes keys easy to
+//! recognize, eg. by secret scanners, the id is used to look up the key and
+//! only an [argon2] hash of the secret is stored.
+//!
+//! A presented key results in a [BoardingPass](super::boarding_pass::BoardingPass)
+//! whose `sub` is the owning passport and whose `services` are the ones of the
+//! key. Keys of passports rejected by the status policy of the managed
+//! passport register are refused. Use the [Traveler](super::traveler::Traveler)
+//! guard to obtain the owning [Passport].
+//!
+//! ```rust,ignore
+//! let api_keys = ApiKeyStore::new(MemoryApiKeyRegister::default());
+//! let key = api_keys.issue_api_key("cosmo", &passport, &["billing"], None)?;
+//!
+//! rocket::build().manage(api_keys).manage(passport_register)
+//! ```
+use super::{
+    error::Error,
+    passport::Passport,
+};
+use anyhow::anyhow;
+use argon2::{
+    password_hash::{
+        rand_core::OsRng,
+        Encoding,
+        PasswordHash,
+        PasswordHasher,
+        PasswordVerifier,
+        SaltString,
+    },
+    Argon2,
+};
+use chrono::{
+    DateTime,
+    Utc,
+};
+use log::debug;
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc,
+        RwLock,
+    },
+};
+
+/// Options for the [ApiKey](super::auth_type::ApiKey) [AuthType](super::auth_type::AuthType).
+/// Manage an instance with [rocket] to change the defaults.
+#[derive(Clone, Debug)]
+pub struct ApiKeyOptions {
+    /// The name of the header that contains the key.
+    pub header: String,
+}
+
+impl Default for ApiKeyOptions {
+    /// Default implementation:
+    ///
+    /// - Header: `X-API-Key`
+    fn default() -> Self {
+        Self {
+            header: "X-API-Key".to_string(),
+        }
+    }
+}
+
+/// A stored API key. The secret part is only contained as hash.
+#[derive(Clone, Debug)]
+pub struct ApiKeyEntry {
+    /// The visible prefix of the key.
+    pub prefix: String,
+    /// The id of the key.
+    pub id: String,
+    hash: String,
+    /// The [id](Passport::id) of the passport that owns the key.
+    pub passport_id: String,
+    /// The services the key is valid for, a subset of the services of the
+    /// owning passport.
+    pub services: Vec<String>,
+    /// Timestamp after which the key is not accepted anymore.
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Whether the key has been revoked.
+    pub revoked: bool,
+    /// Timestamp when the key has been created.
+    pub created_at: DateTime<Utc>,
+}
+
+impl ApiKeyEntry {
+    /// Generates a new key for the given passport. Returns the key in plain
+    /// text together with the entry that needs to be stored.
+    ///
+    /// The given `services` need to be a subset of the services of the
+    /// passport.
+    pub fn generate(
+        prefix: &str,
+        passport: &Passport,
+        services: &[&str],
+        expires_at: Option<DateTime<Utc>>,
+    ) -> anyhow::Result<(String, Self)> {
+        if prefix.is_empty() || !prefix.chars().all(|c| c.is_alphanumeric()) {
+            return Err(anyhow!(
+                "The prefix of an API key must be alphanumeric."
+            ));
+        }
+        if let Some(service) = services
+            .iter()
+            .find(|s| !passport.services().iter().any(|p| p == *s))
+        {
+            return Err(anyhow!(
+                "Passport {} does not have service {service}.",
+                passport.id
+            ));
+        }
+        let id = generate(12);
+        let secret = generate(32);
+        let entry = Self {
+            prefix: prefix.to_string(),
+            id: id.clone(),
+            hash: Self::hash_secret(&secret)?,
+            passport_id: passport.id.clone(),
+            services: services.iter().map(|s| s.to_string()).collect(),
+            expires_at,
+            revoked: false,
+            created_at: Utc::now(),
+        };
+        Ok((format!("{prefix}_{id}_{secret}"), entry))
+    }
+
+    /// Restores an entry from its stored values, eg. when loading it from a
+    /// database.
+    #[allow(clippy::too_many_arguments)]
+    pub fn from_parts(
+        prefix: &str,
+        id: &str,
+        hash: &str,
+        passport_id: &str,
+        services: Vec<String>,
+        expires_at: Option<DateTime<Utc>>,
+        revoked: bool,
+        created_at: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            prefix: prefix.to_string(),
+            id: id.to_string(),
+            hash: hash.to_string(),
+            passport_id: passport_id.to_string(),
+            services,
+            expires_at,
+            revoked,
+            created_at,
+        }
+    }
+
+    /// Returns the hash of the secret part.
+    pub fn hash(&self) -> &str {
+        &self.hash
+    }
+
+    /// Returns `true` if the key is neither revoked nor expired.
+    pub fn is_valid(&self) -> bool {
+        !self.revoked && self.expires_at.map(|e| e > Utc::now()).unwrap_or(true)
+    }
+
+    /// Checks if the given secret is correct.
+    pub fn verify_secret(&self, secret: &str) -> anyhow::Result<bool> {
+        let hash = PasswordHash::parse(&self.hash, Encoding::B64)
+            .map_err(|e| anyhow!("{e}"))?;
+        Ok(Argon2::default()
+            .verify_password(secret.as_bytes(), &hash)
+            .is_ok())
+    }
+
+    /// Splits the given key into prefix, id and secret.
+    pub fn split(key: &str) -> Option<(&str, &str, &str)> {
+        let mut parts = key.rsplitn(3, '_');
+        let secret = parts.next()?;
+        let id = parts.next()?;
+        let prefix = parts.next()?;
+        Some((prefix, id, secret))
+    }
+
+    /// Hashes the secret using `[argon2]`.
+    fn hash_secret(secret: &str) -> anyhow::Result<String> {
+        let salt = SaltString::generate(&mut OsRng);
+        Ok(Argon2::default()
+            .hash_password(secret.as_bytes(), &salt)
+            .map_err(|e| anyhow!("{e}"))?
+            .to_string())
+    }
+}
+
+/// The API key register contains all API keys known to your application.
+pub trait ApiKeyRegister {
+    /// Returns the key with the given id.
+    fn api_key(&self, key_id: &str) -> Result<Option<ApiKeyEntry>, Error>;
+    /// Returns all keys of the given passport.
+    fn api_keys(&self, passport_id: &str) -> Result<Vec<ApiKeyEntry>, Error>;
+    /// Stores the given key.
+    fn store_api_key(&self, entry: ApiKeyEntry) -> Result<(), Error>;
+    /// Revokes the key with the given id.
+    fn revoke_api_key(&self, key_id: &str) -> Result<(), Error>;
+
+    /// Generates and stores a new key for the given passport, returning the
+    /// key in plain text. The key can not be recovered afterwards.
+    fn issue_api_key(
+        &self,
+        prefix: &str,
+        passport: &Passport,
+        services: &[&str],
+        expires_at: Option<DateTime<Utc>>,
+    ) -> Result<String, Error> {
+        let (key, entry) =
+            ApiKeyEntry::generate(prefix, passport, services, expires_at)?;
+        self.store_api_key(entry)?;
+        Ok(key)
+    }
+
+    /// Verifies the given key.
+    /// Return scenarios are the following:
+    /// - If valid, the corresponding entry is returned.
+    /// - If no corresponding entry is found, the return value is `Ok(None)`.
+    /// - If the key is malformed, wrong, revoked or expired, it returns
+    ///   [Error::InvalidCredentials].
+    /// - In all other cases, it returns `Err(_)`.
+    fn verify_api_key(&self, key: &str) -> Result<Option<ApiKeyEntry>, Error> {
+        let Some((prefix, id, secret)) = ApiKeyEntry::split(key) else {
+            debug!("Malformed API key.");
+            return Err(Error::InvalidCredentials);
+        };
+        let Some(entry) = self.api_key(id)? else {
+            debug!("API key with id {id} not found.");
+            return Ok(None);
+        };
+        if entry.prefix != prefix || !entry.verify_secret(secret)? {
+            debug!("Invalid secret for API key with id {id}.");
+            return Err(Error::InvalidCredentials);
+        }
+        if entry.revoked {
+            debug!("API key with id {id} has been revoked.");
+            return Err(Error::InvalidCredentials);
+        }
+        if !entry.is_valid() {
+            debug!("API key with id {id} has expired.");
+            return Err(Error::InvalidCredentials);
+        }
+        Ok(Some(entry))
+    }
+}
+
+/// An [ApiKeyRegister] where all keys are stored in memory.
+#[derive(Default)]
+pub struct MemoryApiKeyRegister {
+    keys: RwLock<HashMap<String, ApiKeyEntry>>,
+}
+
+impl ApiKeyRegister for MemoryApiKeyRegister {
+    fn api_key(&self, key_id: &str) -> Result<Option<ApiKeyEntry>, Error> {
+        Ok(self
+            .keys
+            .read()
+            .map_err(|e| {
+                Error::storage(format!("Could not read API keys: {e}"))
+            })?
+            .get(key_id)
+            .cloned())
+    }
+    fn api_keys(&self, passport_id: &str) -> Result<Vec<ApiKeyEntry>, Error> {
+        Ok(self
+            .keys
+            .read()
+            .map_err(|e| {
+                Error::storage(format!("Could not read API keys: {e}"))
+            })?
+            .values()
+            .filter(|k| k.passport_id == passport_id)
+            .cloned()
+            .collect())
+    }
+    fn store_api_key(&self, entry: ApiKeyEntry) -> Result<(), Error> {
+        self.keys
+            .write()
+            .map_err(|e| {
+                Error::storage(format!("Could not write API keys: {e}"))
+            })?
+            .insert(entry.id.clone(), entry);
+        Ok(())
+    }
+    fn revoke_api_key(&self, key_id: &str) -> Result<(), Error> {
+        let mut keys = self.keys.write().map_err(|e| {
+            Error::storage(format!("Could not write API keys: {e}"))
+        })?;
+        let Some(entry) = keys.get_mut(key_id) else {
+            return Err(Error::Other(anyhow!("Unknown API key {key_id}.")));
+        };
+        entry.revoked = true;
+        Ok(())
+    }
+}
+
+/// The [ApiKeyRegister] that is used by the request guard of the
+/// [ApiKey](super::auth_type::ApiKey) [AuthType](super::auth_type::AuthType).
+/// Manage an instance with [rocket] to enable API keys.
+///
+/// Cloning is cheap, all clones share the same register.
+#[derive(Clone)]
+pub struct ApiKeyStore {
+    register: Arc<dyn ApiKeyRegister + Send + Sync>,
+}
+
+impl ApiKeyStore {
+    /// Creates a new instance using the given register.
+    pub fn new<AKR>(register: AKR) -> Self
+    where
+        AKR: ApiKeyRegister + Send + Sync + 'static,
+    {
+        Self {
+            register: Arc::new(register),
+        }
+    }
+}
+
+impl ApiKeyRegister for ApiKeyStore {
+    fn api_key(&self, key_id: &str) -> Result<Option<ApiKeyEntry>, Error> {
+        self.register.api_key(key_id)
+    }
+    fn api_keys(&self, passport_id: &str) -> Result<Vec<ApiKeyEntry>, Error> {
+        self.register.api_keys(passport_id)
+    }
+    fn store_api_key(&self, entry: ApiKeyEntry) -> Result<(), Error> {
+        self.register.store_api_key(entry)
+    }
+    fn revoke_api_key(&self, key_id: &str) -> Result<(), Error> {
+        self.register.revoke_api_key(key_id)
+    }
+}
+
+/// Generates a random alphanumeric string of the given length.
+fn generate(length: usize) -> String {
+    use rand::{
+        distributions::Alphanumeric,
+        thread_rng,
+        Rng,
+    };
+
+    thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(length)
+        .map(char::from)
+        .collect()
+}
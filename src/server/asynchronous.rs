@@ -0,0 +1,467 @@
+//! Asynchronous variants of [PassportRegister], [BoardingPassStorage] and
+//! the [Gate](super::gate::Gate).
+//!
+//! Registers backed by a database, LDAP or a remote service should implement
+//! [AsyncPassportRegister], so they do not block the executor of [rocket].
+//! Every [PassportRegister] and [BoardingPassStorage] implements the
+//! asynchronous variant as well, so both can be used with an [AsyncGate].
+//!
+//! Verifying a password using [argon2] is expensive. The default
+//! implementation of [AsyncPassportRegister::verify_credentials] runs it using
+//! [spawn_blocking](rocket::tokio::task::spawn_blocking), so a burst of logins
+//! does not stall other requests. A [PassportRegister] verifies the
+//! credentials itself, using
+//! [block_in_place](rocket::tokio::task::block_in_place) on a multi-threaded
+//! runtime. Use [blocking] to hash a password off the executor, eg. while
+//! creating a [Passport].
+//!
+//! ```rust,ignore
+//! let passport = JwtBearerGate::login(ticket, register, &storage).await?;
+//! ```
+//!
+//! Both, the synchronous and the asynchronous traits use the same method
+//! names. Import only the ones you need to avoid ambiguous calls.
+use super::{
+    auth_type::{
+        AuthType,
+        Bearer,
+        Cookie,
+        Session,
+    },
+    boarding_pass::{
+        payloads::JsonWebToken,
+        BoardingPass,
+    },
+    error::Error,
+    gate::{
+        admit,
+        JwtBearerGate,
+        JwtCookieGate,
+        SessionGate,
+    },
+    passport::{
+        Passport,
+        PassportStatusPolicy,
+    },
+    passport_register::{
+        check_id,
+        PassportRegister,
+    },
+    refresh::{
+        RefreshTokenStorage,
+        TokenPair,
+    },
+    storage::BoardingPassStorage,
+};
+use crate::Ticket;
+use anyhow::anyhow;
+use log::debug;
+use rocket::tokio::{
+    runtime::{
+        Handle,
+        RuntimeFlavor,
+    },
+    task,
+};
+use std::future::Future;
+
+/// The asynchronous variant of [PassportRegister].
+///
+/// Every [PassportRegister] is an [AsyncPassportRegister]. As the register is
+/// borrowed, it can not be moved to a blocking thread. Its
+/// [verify_credentials](PassportRegister::verify_credentials) is therefore
+/// called using [block_in_place](rocket::tokio::task::block_in_place), so
+/// other requests are moved to another worker while it hashes. All other
+/// methods are called directly, registers that block on I/O should implement
+/// this trait themselves.
+pub trait AsyncPassportRegister: Send + Sync {
+    /// See [PassportRegister::passport].
+    fn passport(
+        &self,
+        passport_id: &str,
+    ) -> impl Future<Output = Result<Option<Passport>, Error>> + Send;
+    /// See [PassportRegister::passports].
+    fn passports(
+        &self,
+    ) -> impl Future<Output = Result<Vec<Passport>, Error>> + Send;
+    /// See [PassportRegister::set_passport].
+    fn set_passport(
+        &self,
+        passport: Passport,
+    ) -> impl Future<Output = Result<String, Error>> + Send;
+    /// See [PassportRegister::remove_passport].
+    fn remove_passport(
+        &self,
+        passport_id: &str,
+    ) -> impl Future<Output = Result<Option<Passport>, Error>> + Send;
+
+    /// See [PassportRegister::update_passport].
+    ///
+    /// The default implementation reads the passport and stores it again
+    /// using [set_passport](Self::set_passport), which is not atomic.
+    fn update_passport(
+        &self,
+        passport_id: &str,
+        update: &mut (dyn FnMut(&mut Passport) -> Result<(), Error> + Send),
+    ) -> impl Future<Output = Result<Option<Passport>, Error>> + Send {
+        async move {
+            let Some(mut passport) = self.passport(passport_id).await? else {
+                return Ok(None);
+            };
+            update(&mut passport)?;
+            check_id(passport_id, &passport)?;
+            self.set_passport(passport.clone()).await?;
+            Ok(Some(passport))
+        }
+    }
+
+    /// See [PassportRegister::verify_credentials].
+    ///
+    /// The default implementation looks up the passport and verifies the
+    /// password on a blocking thread.
+    fn verify_credentials(
+        &self,
+        ticket: &Ticket,
+    ) -> impl Future<Output = Result<Option<Passport>, Error>> + Send {
+        verify(self, ticket)
+    }
+
+    /// See [PassportRegister::status_policy].
+    fn status_policy(&self) -> PassportStatusPolicy {
+        PassportStatusPolicy::default()
+    }
+}
+
+impl<PR> AsyncPassportRegister for PR
+where
+    PR: PassportRegister + Send + Sync,
+{
+    async fn passport(
+        &self,
+        passport_id: &str,
+    ) -> Result<Option<Passport>, Error> {
+        PassportRegister::passport(self, passport_id)
+    }
+    async fn passports(&self) -> Result<Vec<Passport>, Error> {
+        PassportRegister::passports(self)
+    }
+    async fn set_passport(&self, passport: Passport) -> Result<String, Error> {
+        PassportRegister::set_passport(self, passport)
+    }
+    async fn remove_passport(
+        &self,
+        passport_id: &str,
+    ) -> Result<Option<Passport>, Error> {
+        PassportRegister::remove_passport(self, passport_id)
+    }
+    async fn update_passport(
+        &self,
+        passport_id: &str,
+        update: &mut (dyn FnMut(&mut Passport) -> Result<(), Error> + Send),
+    ) -> Result<Option<Passport>, Error> {
+        PassportRegister::update_passport(self, passport_id, update)
+    }
+    async fn verify_credentials(
+        &self,
+        ticket: &Ticket,
+    ) -> Result<Option<Passport>, Error> {
+        block_in_place(|| PassportRegister::verify_credentials(self, ticket))
+    }
+    fn status_policy(&self) -> PassportStatusPolicy {
+        PassportRegister::status_policy(self)
+    }
+}
+
+/// The asynchronous variant of [BoardingPassStorage].
+///
+/// Every [BoardingPassStorage] is an [AsyncBoardingPassStorage].
+pub trait AsyncBoardingPassStorage<BPD, AT, ID, ENC>: Send + Sync
+where
+    AT: AuthType,
+{
+    /// See [BoardingPassStorage::boarding_pass].
+    fn boarding_pass(
+        &self,
+        identifier: ID,
+    ) -> impl Future<Output = Result<Option<BoardingPass<BPD, AT>>, Error>> + Send;
+    /// See [BoardingPassStorage::store_boarding_pass].
+    fn store_boarding_pass(
+        &self,
+        boarding_pass: &BoardingPass<BPD, AT>,
+    ) -> impl Future<Output = Result<ENC, Error>> + Send;
+    /// See [BoardingPassStorage::remove_boarding_pass].
+    fn remove_boarding_pass(
+        &self,
+        identifier: ID,
+    ) -> impl Future<Output = Result<(), Error>> + Send;
+    /// See [BoardingPassStorage::issue_boarding_pass].
+    fn issue_boarding_pass(
+        &self,
+        passport: &Passport,
+    ) -> impl Future<Output = Result<BoardingPass<BPD, AT>, Error>> + Send;
+}
+
+impl<BPS, BPD, AT, ID, ENC> AsyncBoardingPassStorage<BPD, AT, ID, ENC> for BPS
+where
+    BPS: BoardingPassStorage<BPD, AT, ID, ENC> + Send + Sync,
+    BPD: Sync,
+    AT: AuthType + Sync,
+    ID: Send,
+{
+    async fn boarding_pass(
+        &self,
+        identifier: ID,
+    ) -> Result<Option<BoardingPass<BPD, AT>>, Error> {
+        BoardingPassStorage::boarding_pass(self, identifier)
+    }
+    async fn store_boarding_pass(
+        &self,
+        boarding_pass: &BoardingPass<BPD, AT>,
+    ) -> Result<ENC, Error> {
+        BoardingPassStorage::store_boarding_pass(self, boarding_pass)
+    }
+    async fn remove_boarding_pass(&self, identifier: ID) -> Result<(), Error> {
+        BoardingPassStorage::remove_boarding_pass(self, identifier)
+    }
+    async fn issue_boarding_pass(
+        &self,
+        passport: &Passport,
+    ) -> Result<BoardingPass<BPD, AT>, Error> {
+        BoardingPassStorage::issue_boarding_pass(self, passport)
+    }
+}
+
+/// The asynchronous variant of [Gate::login](super::gate::Gate::login) and
+/// [Gate::refresh](super::gate::Gate::refresh).
+pub trait AsyncGate<BPD, T, ID, ENC>
+where
+    T: AuthType + Send + Sync,
+    BPD: Send + Sync,
+    ENC: Send,
+{
+    /// Checks if the given [Ticket] is valid and generates a [BoardingPass] on
+    /// success.
+    fn login<BPS, PR>(
+        ticket: Ticket,
+        passport_register: &PR,
+        boarding_pass_storage: &BPS,
+    ) -> impl Future<Output = Result<ENC, Error>> + Send
+    where
+        BPS: AsyncBoardingPassStorage<BPD, T, ID, ENC>,
+        PR: AsyncPassportRegister,
+    {
+        async move {
+            let passport = Self::check_in(&ticket, passport_register).await?;
+            Self::board(&passport, boarding_pass_storage).await
+        }
+    }
+
+    /// See [Gate::check_in](super::gate::Gate::check_in).
+    fn check_in<PR>(
+        ticket: &Ticket,
+        passport_register: &PR,
+    ) -> impl Future<Output = Result<Passport, Error>> + Send
+    where
+        PR: AsyncPassportRegister,
+    {
+        async move {
+            let Some(passport) =
+                passport_register.verify_credentials(ticket).await?
+            else {
+                return Err(Error::PassportNotFound(ticket.id.clone()));
+            };
+            passport_register.status_policy().check(&passport)?;
+            Ok(passport)
+        }
+    }
+
+    /// See [Gate::board](super::gate::Gate::board).
+    fn board<BPS>(
+        passport: &Passport,
+        boarding_pass_storage: &BPS,
+    ) -> impl Future<Output = Result<ENC, Error>> + Send
+    where
+        BPS: AsyncBoardingPassStorage<BPD, T, ID, ENC>,
+    {
+        async move {
+            let boarding_pass: BoardingPass<BPD, T> =
+                boarding_pass_storage.issue_boarding_pass(passport).await?;
+            boarding_pass_storage
+                .store_boarding_pass(&boarding_pass)
+                .await
+        }
+    }
+
+    /// See [Gate::refresh](super::gate::Gate::refresh).
+    fn refresh<BPS, PR, RTS>(
+        refresh_token: &str,
+        passport_register: &PR,
+        boarding_pass_storage: &BPS,
+        refresh_token_storage: &RTS,
+    ) -> impl Future<Output = Result<TokenPair<ENC>, Error>> + Send
+    where
+        BPS: AsyncBoardingPassStorage<BPD, T, ID, ENC>,
+        PR: AsyncPassportRegister,
+        RTS: RefreshTokenStorage + Sync,
+    {
+        async move {
+            let refresh_token =
+                refresh_token_storage.rotate_refresh_token(refresh_token)?;
+            let passport = admit(
+                refresh_token_storage,
+                &refresh_token,
+                passport_register
+                    .passport(&refresh_token.passport_id)
+                    .await?,
+                passport_register.status_policy(),
+            )?;
+            let access_token =
+                Self::board(&passport, boarding_pass_storage).await?;
+            Ok(TokenPair {
+                access_token,
+                refresh_token: refresh_token.token,
+            })
+        }
+    }
+}
+
+impl AsyncGate<JsonWebToken, Cookie, (), String> for JwtCookieGate {}
+
+impl AsyncGate<JsonWebToken, Bearer, (), String> for JwtBearerGate {}
+
+impl AsyncGate<JsonWebToken, Session, String, String> for SessionGate {}
+
+/// Runs the given password hashing or verification on a blocking thread.
+///
+/// ```rust,ignore
+/// let passport = blocking(move || {
+///     Passport::new(&id, &password, &[], PassportType::User)
+/// })
+/// .await?;
+/// ```
+pub async fn blocking<F, R>(f: F) -> Result<R, Error>
+where
+    F: FnOnce() -> anyhow::Result<R> + Send + 'static,
+    R: Send + 'static,
+{
+    task::spawn_blocking(f)
+        .await
+        .map_err(|e| Error::Other(anyhow!("Blocking task failed: {e}")))?
+        .map_err(Error::from)
+}
+
+/// Calls `f` using [block_in_place](task::block_in_place) on a
+/// multi-threaded runtime, otherwise calls it directly.
+fn block_in_place<F, R>(f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    match Handle::try_current() {
+        Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => {
+            task::block_in_place(f)
+        }
+        _ => f(),
+    }
+}
+
+/// Looks up the passport of the given [Ticket] and verifies its password on a
+/// blocking thread.
+async fn verify<PR>(
+    passport_register: &PR,
+    ticket: &Ticket,
+) -> Result<Option<Passport>, Error>
+where
+    PR: AsyncPassportRegister + ?Sized,
+{
+    let Some(passport) = passport_register.passport(&ticket.id).await? else {
+        debug!("User with id {} not found.", ticket.id);
+        return Ok(None);
+    };
+    let secret = ticket.secret.clone();
+    let (passport, valid) = blocking(move || {
+        let valid = passport.verify_password(&secret)?;
+        Ok((passport, valid))
+    })
+    .await?;
+    if valid {
+        Ok(Some(passport))
+    } else {
+        Err(Error::InvalidCredentials)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::{
+        passport::PassportType,
+        passport_register::MemoryPassportRegister,
+    };
+
+    /// A register that delegates verification, eg. to a directory service,
+    /// and accepts every secret of a known passport.
+    struct DelegatingRegister(MemoryPassportRegister);
+
+    impl PassportRegister for DelegatingRegister {
+        fn passport(
+            &self,
+            passport_id: &str,
+        ) -> Result<Option<Passport>, Error> {
+            PassportRegister::passport(&self.0, passport_id)
+        }
+        fn passports(&self) -> Result<Vec<Passport>, Error> {
+            PassportRegister::passports(&self.0)
+        }
+        fn set_passport(&self, passport: Passport) -> Result<String, Error> {
+            PassportRegister::set_passport(&self.0, passport)
+        }
+        fn remove_passport(
+            &self,
+            passport_id: &str,
+        ) -> Result<Option<Passport>, Error> {
+            PassportRegister::remove_passport(&self.0, passport_id)
+        }
+        fn verify_credentials(
+            &self,
+            ticket: &Ticket,
+        ) -> Result<Option<Passport>, Error> {
+            PassportRegister::passport(self, &ticket.id)
+        }
+    }
+
+    fn register() -> DelegatingRegister {
+        let passport = Passport::from_parts(
+            "cosmo",
+            "",
+            vec![],
+            PassportType::User,
+            false,
+            true,
+            chrono::Utc::now(),
+        );
+        DelegatingRegister(MemoryPassportRegister::from(vec![passport]))
+    }
+
+    async fn verifies_using_register() {
+        let register = register();
+        let ticket = Ticket::new("cosmo", "not the password");
+        let passport =
+            AsyncPassportRegister::verify_credentials(&register, &ticket)
+                .await
+                .unwrap();
+        assert_eq!(passport.map(|p| p.id), Some("cosmo".to_string()));
+    }
+
+    #[rocket::async_test]
+    async fn forwards_verification_on_current_thread_runtime() {
+        verifies_using_register().await;
+    }
+
+    #[test]
+    fn forwards_verification_on_multi_thread_runtime() {
+        rocket::tokio::runtime::Builder::new_multi_thread()
+            .build()
+            .unwrap()
+            .block_on(verifies_using_register());
+    }
+}
@@ -1,4 +1,11 @@
 //! Different auth methods, eg. `Cookie` or `Bearer`.
+use super::passport_register::MemoryPassportRegister;
+use crate::Ticket;
+use base64::{
+    engine::general_purpose::STANDARD,
+    Engine,
+};
+use std::marker::PhantomData;
 
 /// The auth type to be used.
 pub trait AuthType {}
@@ -10,30 +17,285 @@ pub struct Cookie;
 
 impl AuthType for Cookie {}
 
+/// Using the [Session] [AuthType] requires a
+/// [SessionStore](crate::session::SessionStore) in [rocket]s global state.
+#[derive(Debug)]
+pub struct Session;
+
+impl AuthType for Session {}
+
+/// Using the [ApiKey] [AuthType] requires an
+/// [ApiKeyStore](crate::api_key::ApiKeyStore) and the
+/// [PassportRegister](crate::passport_register::PassportRegister) `PR` in
+/// [rocket]s global state. The passport owning the key is checked against the
+/// status policy of the register on every request.
+pub struct ApiKey<PR = MemoryPassportRegister> {
+    phantom_register: PhantomData<fn() -> PR>,
+}
+
+impl<PR> std::fmt::Debug for ApiKey<PR> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ApiKey").finish()
+    }
+}
+
+impl<PR> AuthType for ApiKey<PR> {}
+
 /// Using the [Bearer] [AuthType] requires a [JwtCipher](crate::ciphering::JwtCipher) in
-/// [rocket]s global state.
+/// [rocket]s global state. The token is extracted as configured by the managed
+/// [BearerOptions].
 #[derive(Debug)]
 pub struct Bearer;
 
 impl Bearer {
     /// Extracts that value from the given slice with respect to the given prefix.
+    ///
+    /// The `Bearer` scheme is matched case-insensitive.
     pub fn extract_value(
         authorization_header: &str,
         prefix: Option<String>,
     ) -> Option<String> {
-        if !authorization_header.starts_with("Bearer ") {
-            return None;
+        BearerOptions {
+            prefix,
+            ..Default::default()
         }
+        .extract_value(authorization_header)
+    }
+}
+
+impl AuthType for Bearer {}
+
+/// Options for the [Bearer] [AuthType]. Manage an instance with [rocket] to
+/// change the defaults.
+///
+/// Browsers can not set headers on WebSocket upgrades, so the token can
+/// optionally be taken from a query parameter or from the
+/// `Sec-WebSocket-Protocol` header instead. These fallbacks are only used if
+/// the header is missing or uses another scheme.
+#[derive(Clone, Debug)]
+pub struct BearerOptions {
+    /// The name of the header that contains the token.
+    pub header: String,
+    /// The authentication scheme, matched case-insensitive.
+    pub scheme: String,
+    /// A prefix that the token needs to start with. It is removed before the
+    /// token is decoded.
+    pub prefix: Option<String>,
+    /// The name of a query parameter that contains the token.
+    pub query_parameter: Option<String>,
+    /// A subprotocol that marks the token in the `Sec-WebSocket-Protocol`
+    /// header, eg. `access_token` for `Sec-WebSocket-Protocol: access_token,
+    /// <token>`. Your WebSocket handler needs to select this subprotocol in
+    /// its response.
+    pub websocket_protocol: Option<String>,
+    /// The realm that is sent in the `WWW-Authenticate` challenge.
+    pub realm: String,
+}
 
-        let Some(token) = authorization_header.strip_prefix("Bearer ") else {
+impl Default for BearerOptions {
+    /// Default implementation:
+    ///
+    /// - Header: `Authorization`
+    /// - Scheme: `Bearer`
+    /// - Prefix: None
+    /// - Query parameter: None
+    /// - WebSocket protocol: None
+    /// - Realm: `cosmodrome`
+    fn default() -> Self {
+        Self {
+            header: "Authorization".to_string(),
+            scheme: "Bearer".to_string(),
+            prefix: None,
+            query_parameter: None,
+            websocket_protocol: None,
+            realm: "cosmodrome".to_string(),
+        }
+    }
+}
+
+impl BearerOptions {
+    /// Extracts the token from the given header value with respect to the
+    /// scheme and the prefix.
+    pub fn extract_value(&self, header_value: &str) -> Option<String> {
+        let (scheme, token) = header_value.split_once(' ')?;
+        if !scheme.eq_ignore_ascii_case(&self.scheme) {
             return None;
+        }
+        self.strip_prefix(token.trim())
+    }
+
+    /// Extracts the token from the given `Sec-WebSocket-Protocol` header
+    /// value, where it follows the configured
+    /// [websocket_protocol](Self::websocket_protocol).
+    pub fn extract_websocket_value(
+        &self,
+        header_value: &str,
+    ) -> Option<String> {
+        let marker = self.websocket_protocol.as_ref()?;
+        let mut protocols = header_value.split(',').map(str::trim);
+        protocols.find(|p| p == marker)?;
+        self.strip_prefix(protocols.next()?)
+    }
+
+    /// Removes the prefix from the given token, if one is configured.
+    pub fn strip_prefix(&self, token: &str) -> Option<String> {
+        let token = match &self.prefix {
+            Some(p) => token.strip_prefix(p.as_str())?,
+            None => token,
         };
-        if let Some(p) = prefix {
-            token.strip_prefix(&p).map(|t| t.to_string())
-        } else {
-            Some(token.to_string())
+        if token.is_empty() {
+            return None;
         }
+        Some(token.to_string())
     }
 }
 
-impl AuthType for Bearer {}
+/// Using the [Basic] [AuthType] verifies the credentials of an
+/// `Authorization: Basic` header on every request against the
+/// [PassportRegister](crate::passport_register::PassportRegister) `PR` in
+/// [rocket]s global state.
+///
+/// Rejected requests carry a `WWW-Authenticate` challenge using the realm of
+/// the managed [BasicOptions], if the
+/// [WwwAuthenticate](crate::challenge::WwwAuthenticate) fairing is attached.
+pub struct Basic<PR = MemoryPassportRegister> {
+    phantom_register: PhantomData<fn() -> PR>,
+}
+
+impl<PR> std::fmt::Debug for Basic<PR> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Basic").finish()
+    }
+}
+
+impl Basic {
+    /// Decodes the credentials of the given `Authorization` header value into
+    /// a [Ticket].
+    pub fn extract_ticket(authorization_header: &str) -> Option<Ticket> {
+        let (scheme, credentials) = authorization_header.split_once(' ')?;
+        if !scheme.eq_ignore_ascii_case("Basic") {
+            return None;
+        }
+        let credentials = STANDARD.decode(credentials.trim()).ok()?;
+        let credentials = String::from_utf8(credentials).ok()?;
+        let (id, secret) = credentials.split_once(':')?;
+        Some(Ticket::new(id, secret))
+    }
+}
+
+impl<PR> AuthType for Basic<PR> {}
+
+/// Options for the [Basic] [AuthType]. Manage an instance with [rocket] to
+/// change the defaults.
+#[derive(Clone, Debug)]
+pub struct BasicOptions {
+    /// The realm that is sent in the `WWW-Authenticate` challenge.
+    pub realm: String,
+}
+
+impl Default for BasicOptions {
+    /// Default implementation:
+    ///
+    /// - Realm: `cosmodrome`
+    fn default() -> Self {
+        Self {
+            realm: "cosmodrome".to_string(),
+        }
+    }
+}
+
+/// Using the [CookieOrBearer] [AuthType] accepts a [BoardingPass](crate::boarding_pass::BoardingPass)
+/// from either a [Cookie] or a [Bearer] token, tried in the order of the managed
+/// [CookieOrBearerOptions]. Requests carrying a cookie and a bearer token of
+/// different passports are rejected.
+///
+/// Requires a [JwtCipher](crate::ciphering::JwtCipher) in [rocket]s global
+/// state.
+#[derive(Debug)]
+pub struct CookieOrBearer;
+
+impl AuthType for CookieOrBearer {}
+
+/// The source that authenticated a request using [CookieOrBearer]. Can be
+/// used as request guard.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum AuthSource {
+    /// The [BoardingPass](crate::boarding_pass::BoardingPass) has been taken
+    /// from the cookie.
+    Cookie,
+    /// The [BoardingPass](crate::boarding_pass::BoardingPass) has been taken
+    /// from the `Authorization` header.
+    Bearer,
+}
+
+/// Options for the [CookieOrBearer] [AuthType]. Manage an instance with
+/// [rocket] to change the defaults.
+#[derive(Clone, Debug)]
+pub struct CookieOrBearerOptions {
+    /// The order in which the sources are tried. The first available source
+    /// authenticates the request.
+    pub order: [AuthSource; 2],
+}
+
+impl Default for CookieOrBearerOptions {
+    /// Default implementation:
+    ///
+    /// - Order: [AuthSource::Bearer], [AuthSource::Cookie]
+    fn default() -> Self {
+        Self {
+            order: [AuthSource::Bearer, AuthSource::Cookie],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bearer_value_is_extracted() {
+        let options = BearerOptions::default();
+        assert_eq!(
+            options.extract_value("Bearer abc"),
+            Some("abc".to_string())
+        );
+        assert_eq!(
+            options.extract_value("bEaReR abc"),
+            Some("abc".to_string())
+        );
+        assert_eq!(
+            options.extract_value("Bearer  abc "),
+            Some("abc".to_string())
+        );
+        assert_eq!(options.extract_value("Bearer "), None);
+        assert_eq!(options.extract_value("Bearer"), None);
+        assert_eq!(options.extract_value("Basic abc"), None);
+    }
+
+    #[test]
+    fn bearer_prefix_is_required_and_removed() {
+        let prefix = Some("cd_".to_string());
+        assert_eq!(
+            Bearer::extract_value("Bearer cd_abc", prefix.clone()),
+            Some("abc".to_string())
+        );
+        assert_eq!(Bearer::extract_value("Bearer abc", prefix.clone()), None);
+        assert_eq!(Bearer::extract_value("Bearer cd_", prefix), None);
+    }
+
+    #[test]
+    fn websocket_value_follows_protocol() {
+        let options = BearerOptions {
+            websocket_protocol: Some("access_token".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            options.extract_websocket_value("chat, access_token, abc"),
+            Some("abc".to_string())
+        );
+        assert_eq!(options.extract_websocket_value("access_token"), None);
+        assert_eq!(options.extract_websocket_value("chat, abc"), None);
+        let options = BearerOptions::default();
+        assert_eq!(options.extract_websocket_value("access_token, abc"), None);
+    }
+}
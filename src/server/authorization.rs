@@ -0,0 +1,331 @@
+//! Request guards that authorize a [BoardingPass] before a route runs.
+//!
+//! A missing or invalid [BoardingPass] results in `401 Unauthorized`, while a
+//! valid one that lacks the required permission results in `403 Forbidden`.
+//!
+//! The guards read the projected claims of the [JsonWebToken], so the
+//! corresponding values need to be enabled in the
+//! [ClaimsProjection](crate::boarding_pass::payloads::ClaimsProjection) of
+//! your cipher.
+//!
+//! ```rust,ignore
+//! #[get("/users")]
+//! fn users(_pass: RequireRole<ModeratorRole, Cookie>) { }
+//!
+//! struct Billing;
+//!
+//! impl Service for Billing {
+//!     const NAME: &'static str = "billing";
+//! }
+//!
+//! #[get("/invoices")]
+//! fn invoices(_pass: RequireService<Billing, Bearer>) { }
+//! ```
+use super::{
+    auth_type::AuthType,
+    boarding_pass::{
+        payloads::JsonWebToken,
+        BoardingPass,
+    },
+    error::Error,
+    passport::PassportType,
+    problem::fail,
+};
+use log::error;
+use rocket::{
+    request::{
+        FromRequest,
+        Outcome,
+        Request,
+    },
+    Ignite,
+    Rocket,
+    Sentinel,
+};
+use std::{
+    collections::{
+        HashMap,
+        HashSet,
+    },
+    marker::PhantomData,
+};
+
+/// Declares a service at the type level, see [Passport::services](crate::passport::Passport::services).
+pub trait Service {
+    /// The name of the service, as contained in the services of a passport.
+    const NAME: &'static str;
+}
+
+/// Request guard that requires the [Service] `S` to be contained in the
+/// [services](JsonWebToken::services) claim of the [BoardingPass].
+#[derive(Debug)]
+pub struct RequireService<S: Service, AT: AuthType> {
+    /// The [BoardingPass] that has been presented.
+    pub boarding_pass: BoardingPass<JsonWebToken, AT>,
+    phantom_service: PhantomData<S>,
+}
+
+/// Aborts launch if the [BoardingPass] guard would abort.
+impl<S, AT> Sentinel for RequireService<S, AT>
+where
+    S: Service,
+    AT: AuthType,
+    BoardingPass<JsonWebToken, AT>: Sentinel,
+{
+    fn abort(rocket: &Rocket<Ignite>) -> bool {
+        BoardingPass::<JsonWebToken, AT>::abort(rocket)
+    }
+}
+
+#[rocket::async_trait]
+impl<'r, S, AT> FromRequest<'r> for RequireService<S, AT>
+where
+    S: Service + Send + Sync,
+    AT: AuthType + Send + Sync,
+    BoardingPass<JsonWebToken, AT>: FromRequest<'r, Error = Error>,
+{
+    type Error = Error;
+
+    async fn from_request(
+        request: &'r Request<'_>,
+    ) -> Outcome<Self, Self::Error> {
+        let boarding_pass =
+            match request.guard::<BoardingPass<JsonWebToken, AT>>().await {
+                Outcome::Success(b) => b,
+                Outcome::Error(e) => return Outcome::Error(e),
+                Outcome::Forward(s) => return Outcome::Forward(s),
+            };
+        let Some(services) = &boarding_pass.data.services else {
+            error!(
+                "The BoardingPass does not contain any services. Please \
+                 enable ClaimsProjection::services of your cipher."
+            );
+            return fail(
+                request,
+                Error::Forbidden("No services available.".to_string()),
+            );
+        };
+        if !services.iter().any(|s| s == S::NAME) {
+            return fail(
+                request,
+                Error::Forbidden(format!("Service {} is required.", S::NAME)),
+            );
+        }
+        Outcome::Success(Self {
+            boarding_pass,
+            phantom_service: PhantomData,
+        })
+    }
+}
+
+/// Declares a role at the type level, see [PassportType]. Implement it for
+/// your own marker type to require a [PassportType::Custom] role.
+pub trait Role {
+    /// The [PassportType] that is required.
+    fn passport_type() -> PassportType;
+}
+
+/// Requires [PassportType::Admin].
+#[derive(Debug)]
+pub struct AdminRole;
+
+impl Role for AdminRole {
+    fn passport_type() -> PassportType {
+        PassportType::Admin
+    }
+}
+
+/// Requires [PassportType::Moderator].
+#[derive(Debug)]
+pub struct ModeratorRole;
+
+impl Role for ModeratorRole {
+    fn passport_type() -> PassportType {
+        PassportType::Moderator
+    }
+}
+
+/// Requires [PassportType::User].
+#[derive(Debug)]
+pub struct UserRole;
+
+impl Role for UserRole {
+    fn passport_type() -> PassportType {
+        PassportType::User
+    }
+}
+
+/// Defines which [PassportType]s imply others. A passport satisfies a
+/// required role if it has the role itself or one of its (transitive)
+/// parents.
+///
+/// Manage an instance with [rocket] to customize the hierarchy that is used by
+/// [RequireRole], otherwise the default is used.
+#[derive(Clone, Debug)]
+pub struct RoleHierarchy {
+    parents: HashMap<PassportType, Vec<PassportType>>,
+}
+
+impl Default for RoleHierarchy {
+    /// Default implementation:
+    ///
+    /// - [Admin](PassportType::Admin) implies [Moderator](PassportType::Moderator)
+    /// - [Moderator](PassportType::Moderator) implies [User](PassportType::User)
+    fn default() -> Self {
+        Self::flat()
+            .with_parent(PassportType::Moderator, PassportType::Admin)
+            .with_parent(PassportType::User, PassportType::Moderator)
+    }
+}
+
+impl RoleHierarchy {
+    /// Creates a hierarchy where no role implies another.
+    pub fn flat() -> Self {
+        Self {
+            parents: HashMap::new(),
+        }
+    }
+
+    /// Declares that `parent` implies `role`. A role can have multiple
+    /// parents.
+    pub fn with_parent(
+        mut self,
+        role: PassportType,
+        parent: PassportType,
+    ) -> Self {
+        self.parents.entry(role).or_default().push(parent);
+        self
+    }
+
+    /// Returns `true` if a passport having the role `held` satisfies the
+    /// `required` role.
+    pub fn satisfies(
+        &self,
+        held: &PassportType,
+        required: &PassportType,
+    ) -> bool {
+        let mut visited = HashSet::new();
+        let mut pending = vec![required];
+        while let Some(role) = pending.pop() {
+            if role == held {
+                return true;
+            }
+            if !visited.insert(role) {
+                continue;
+            }
+            if let Some(parents) = self.parents.get(role) {
+                pending.extend(parents);
+            }
+        }
+        false
+    }
+}
+
+/// Request guard that requires the [role](JsonWebToken::role) claim of the
+/// [BoardingPass] to satisfy the [Role] `R` with respect to the managed
+/// [RoleHierarchy].
+#[derive(Debug)]
+pub struct RequireRole<R: Role, AT: AuthType> {
+    /// The [BoardingPass] that has been presented.
+    pub boarding_pass: BoardingPass<JsonWebToken, AT>,
+    phantom_role: PhantomData<R>,
+}
+
+/// Aborts launch if the [BoardingPass] guard would abort.
+impl<R, AT> Sentinel for RequireRole<R, AT>
+where
+    R: Role,
+    AT: AuthType,
+    BoardingPass<JsonWebToken, AT>: Sentinel,
+{
+    fn abort(rocket: &Rocket<Ignite>) -> bool {
+        BoardingPass::<JsonWebToken, AT>::abort(rocket)
+    }
+}
+
+#[rocket::async_trait]
+impl<'r, R, AT> FromRequest<'r> for RequireRole<R, AT>
+where
+    R: Role + Send + Sync,
+    AT: AuthType + Send + Sync,
+    BoardingPass<JsonWebToken, AT>: FromRequest<'r, Error = Error>,
+{
+    type Error = Error;
+
+    async fn from_request(
+        request: &'r Request<'_>,
+    ) -> Outcome<Self, Self::Error> {
+        let boarding_pass =
+            match request.guard::<BoardingPass<JsonWebToken, AT>>().await {
+                Outcome::Success(b) => b,
+                Outcome::Error(e) => return Outcome::Error(e),
+                Outcome::Forward(s) => return Outcome::Forward(s),
+            };
+        let Some(role) = &boarding_pass.data.role else {
+            error!(
+                "The BoardingPass does not contain a role. Please enable \
+                 ClaimsProjection::role of your cipher."
+            );
+            return fail(
+                request,
+                Error::Forbidden("No role available.".to_string()),
+            );
+        };
+        let required = R::passport_type();
+        let satisfied = match request.rocket().state::<RoleHierarchy>() {
+            Some(hierarchy) => hierarchy.satisfies(role, &required),
+            None => RoleHierarchy::default().satisfies(role, &required),
+        };
+        if !satisfied {
+            return fail(
+                request,
+                Error::Forbidden(format!("Role {required:?} is required.")),
+            );
+        }
+        Outcome::Success(Self {
+            boarding_pass,
+            phantom_role: PhantomData,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_hierarchy_is_transitive() {
+        let hierarchy = RoleHierarchy::default();
+        let admin = PassportType::Admin;
+        let moderator = PassportType::Moderator;
+        let user = PassportType::User;
+        assert!(hierarchy.satisfies(&admin, &user));
+        assert!(hierarchy.satisfies(&admin, &moderator));
+        assert!(hierarchy.satisfies(&moderator, &user));
+        assert!(hierarchy.satisfies(&user, &user));
+        assert!(!hierarchy.satisfies(&user, &moderator));
+        assert!(!hierarchy.satisfies(&moderator, &admin));
+    }
+
+    #[test]
+    fn flat_hierarchy_only_satisfies_same_role() {
+        let hierarchy = RoleHierarchy::flat();
+        assert!(hierarchy.satisfies(&PassportType::Admin, &PassportType::Admin));
+        assert!(!hierarchy.satisfies(&PassportType::Admin, &PassportType::User));
+    }
+
+    #[test]
+    fn custom_roles_and_cycles() {
+        let editor = PassportType::Custom("Editor".to_string());
+        let reviewer = PassportType::Custom("Reviewer".to_string());
+        let hierarchy = RoleHierarchy::flat()
+            .with_parent(PassportType::User, editor.clone())
+            .with_parent(PassportType::User, reviewer.clone())
+            .with_parent(editor.clone(), reviewer.clone())
+            .with_parent(reviewer.clone(), editor.clone());
+        assert!(hierarchy.satisfies(&editor, &PassportType::User));
+        assert!(hierarchy.satisfies(&reviewer, &editor));
+        assert!(hierarchy.satisfies(&editor, &reviewer));
+        assert!(!hierarchy.satisfies(&PassportType::Admin, &editor));
+    }
+}
@@ -1,25 +1,55 @@
 //! A [BoardingPass] is the piece you need to be granted access to a [rocket].
 use super::{
+    api_key::{
+        ApiKeyOptions,
+        ApiKeyRegister,
+        ApiKeyStore,
+    },
+    asynchronous::{
+        blocking,
+        AsyncPassportRegister,
+    },
     auth_type::{
+        ApiKey,
+        AuthSource,
         AuthType,
+        Basic,
+        BasicOptions,
         Bearer,
+        BearerOptions,
         Cookie,
+        CookieOrBearer,
+        CookieOrBearerOptions,
+        Session,
     },
+    challenge::challenge,
     ciphering::{
-        Ciphering,
-        JwtCipher,
+        decode_managed,
+        is_managed,
+        TokenLifetime,
+        ValidationOptions,
+    },
+    error::Error,
+    problem::fail,
+    revocation::{
+        RevocationList,
+        RevocationStorage,
     },
-    passport::Passport,
-    storage::{
-        BoardingPassStorage,
-        CookieStorageOptions,
-        Storage,
+    session::{
+        SessionBackend,
+        SessionStore,
     },
+    storage::CookieStorageOptions,
+};
+use chrono::{
+    DateTime,
+    Utc,
 };
-use anyhow::anyhow;
-use chrono::TimeDelta;
 use log::error;
-use payloads::JsonWebToken;
+use payloads::{
+    ClaimsProjection,
+    JsonWebToken,
+};
 use rocket::{
     http::Status,
     request::{
@@ -31,6 +61,9 @@ use rocket::{
         Deserialize,
         Serialize,
     },
+    Ignite,
+    Rocket,
+    Sentinel,
 };
 use std::marker::PhantomData;
 
@@ -47,100 +80,710 @@ pub struct BoardingPass<BPD, T: AuthType> {
     phantom_auth: PhantomData<T>,
 }
 
-impl TryFrom<&Passport> for BoardingPass<JsonWebToken, Cookie> {
-    type Error = anyhow::Error;
-    fn try_from(value: &Passport) -> Result<Self, Self::Error> {
-        let valid =
-            TimeDelta::try_weeks(1).ok_or(anyhow!("TimeDelta overflow."))?;
-        Ok(Self {
-            data: JsonWebToken::new(value, valid),
+impl<BPD, T: AuthType> BoardingPass<BPD, T> {
+    /// Creates a new [BoardingPass] containing the given data.
+    pub fn new(data: BPD) -> Self {
+        Self {
+            data,
             phantom_auth: PhantomData,
-        })
+        }
     }
 }
 
-impl TryFrom<&Passport> for BoardingPass<JsonWebToken, Bearer> {
-    type Error = anyhow::Error;
-    fn try_from(value: &Passport) -> Result<Self, Self::Error> {
-        let valid =
-            TimeDelta::try_weeks(1).ok_or(anyhow!("TimeDelta overflow."))?;
-        Ok(Self {
-            data: JsonWebToken::new(value, valid),
-            phantom_auth: PhantomData,
-        })
+/// Aborts launch if a route requires this guard without a managed cipher.
+impl Sentinel for BoardingPass<JsonWebToken, Cookie> {
+    fn abort(rocket: &Rocket<Ignite>) -> bool {
+        abort_without_cipher(rocket)
+    }
+}
+
+/// Aborts launch if a route requires this guard without a managed cipher.
+impl Sentinel for BoardingPass<JsonWebToken, Bearer> {
+    fn abort(rocket: &Rocket<Ignite>) -> bool {
+        abort_without_cipher(rocket)
     }
 }
 
 #[rocket::async_trait]
 impl<'r> FromRequest<'r> for BoardingPass<JsonWebToken, Cookie> {
-    type Error = anyhow::Error;
+    type Error = Error;
 
     async fn from_request(
         request: &'r Request<'_>,
     ) -> Outcome<Self, Self::Error> {
-        let Some(cipher) = request.rocket().state::<JwtCipher>() else {
-            log::error!(
-                "No cosmodrome JwtCipher managed by rocket. Please create an \
-                 instance and manage it with rocket."
-            );
+        if !is_cipher_managed(request) {
             return Outcome::Forward(Status::InternalServerError);
+        }
+        let Some(cookie) = request.cookies().get_private(&cookie_name(request))
+        else {
+            return fail(request, Error::MissingCredentials);
         };
-        let storage = Storage::new(
-            request.cookies(),
-            CookieStorageOptions::default(),
-            cipher.clone(),
+        match decode_managed(request.rocket(), &cookie.value().to_string()) {
+            None => Outcome::Forward(Status::InternalServerError),
+            Some(Err(e)) => fail(request, e),
+            Some(Ok(u)) => check_revocation(request, u),
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for BoardingPass<JsonWebToken, Bearer> {
+    type Error = Error;
+
+    async fn from_request(
+        request: &'r Request<'_>,
+    ) -> Outcome<Self, Self::Error> {
+        if !is_cipher_managed(request) {
+            return Outcome::Forward(Status::InternalServerError);
+        }
+        let options = bearer_options(request);
+        let reject = |error: Option<&str>, e: Error| {
+            let mut value = format!("Bearer realm=\"{}\"", options.realm);
+            if let Some(error) = error {
+                value.push_str(&format!(", error=\"{error}\""));
+            }
+            challenge(request, value);
+            fail(request, e)
+        };
+        let token = match bearer_token(request, &options) {
+            BearerToken::Missing => {
+                return reject(None, Error::MissingCredentials)
+            }
+            BearerToken::Malformed => {
+                return reject(
+                    Some("invalid_request"),
+                    Error::InvalidToken(
+                        "Not a valid Bearer authorization header.".to_string(),
+                    ),
+                )
+            }
+            BearerToken::Present(token) => token,
+        };
+        let outcome = match decode_managed(request.rocket(), &token) {
+            None => return Outcome::Forward(Status::InternalServerError),
+            Some(Err(e)) => Outcome::Error((e.status(), e)),
+            Some(Ok(u)) => check_revocation(request, u),
+        };
+        match outcome {
+            Outcome::Error((status, e)) if status == Status::Unauthorized => {
+                reject(Some("invalid_token"), e)
+            }
+            outcome => outcome,
+        }
+    }
+}
+
+/// Aborts launch if a route requires this guard without a managed
+/// [SessionStore].
+impl Sentinel for BoardingPass<JsonWebToken, Session> {
+    fn abort(rocket: &Rocket<Ignite>) -> bool {
+        if rocket.state::<SessionStore>().is_some() {
+            return false;
+        }
+        error!(
+            "A route requires a session BoardingPass, but no cosmodrome \
+             SessionStore is managed by rocket."
         );
-        let user = match storage.boarding_pass(()) {
+        true
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for BoardingPass<JsonWebToken, Session> {
+    type Error = Error;
+
+    async fn from_request(
+        request: &'r Request<'_>,
+    ) -> Outcome<Self, Self::Error> {
+        let Some(sessions) = request.rocket().state::<SessionStore>() else {
+            error!(
+                "No cosmodrome SessionStore managed by rocket. Please create \
+                 an instance and manage it with rocket."
+            );
+            return Outcome::Forward(Status::InternalServerError);
+        };
+        let Some(cookie) = request.cookies().get_private(&cookie_name(request))
+        else {
+            return fail(request, Error::MissingCredentials);
+        };
+        match sessions.session(cookie.value()) {
+            Err(e) => {
+                error!("{e}");
+                Outcome::Forward(Status::InternalServerError)
+            }
+            Ok(None) => fail(
+                request,
+                Error::InvalidToken("Session not found.".to_string()),
+            ),
+            // Backends are not required to filter expired sessions.
+            Ok(Some(data)) if !data.is_valid() => {
+                fail(request, Error::TokenExpired)
+            }
+            Ok(Some(data)) => {
+                check_revocation(request, BoardingPass::new(data))
+            }
+        }
+    }
+}
+
+/// Aborts launch if a route requires this guard without a managed
+/// [ApiKeyStore] or `PR`.
+impl<PR> Sentinel for BoardingPass<JsonWebToken, ApiKey<PR>>
+where
+    PR: AsyncPassportRegister + 'static,
+{
+    fn abort(rocket: &Rocket<Ignite>) -> bool {
+        if rocket.state::<ApiKeyStore>().is_none() {
+            error!(
+                "A route requires an API key BoardingPass, but no cosmodrome \
+                 ApiKeyStore is managed by rocket."
+            );
+            return true;
+        }
+        if rocket.state::<PR>().is_none() {
+            error!(
+                "A route requires an API key BoardingPass, but no cosmodrome \
+                 PassportRegister of type {} is managed by rocket.",
+                std::any::type_name::<PR>()
+            );
+            return true;
+        }
+        false
+    }
+}
+
+#[rocket::async_trait]
+impl<'r, PR> FromRequest<'r> for BoardingPass<JsonWebToken, ApiKey<PR>>
+where
+    PR: AsyncPassportRegister + 'static,
+{
+    type Error = Error;
+
+    async fn from_request(
+        request: &'r Request<'_>,
+    ) -> Outcome<Self, Self::Error> {
+        let Some(api_keys) = request.rocket().state::<ApiKeyStore>() else {
+            error!(
+                "No cosmodrome ApiKeyStore managed by rocket. Please create \
+                 an instance and manage it with rocket."
+            );
+            return Outcome::Forward(Status::InternalServerError);
+        };
+        let Some(register) = request.rocket().state::<PR>() else {
+            error!(
+                "No cosmodrome PassportRegister managed by rocket. Please \
+                 create an instance and manage it with rocket."
+            );
+            return Outcome::Forward(Status::InternalServerError);
+        };
+        let header = match request.rocket().state::<ApiKeyOptions>() {
+            Some(o) => o.header.clone(),
+            None => ApiKeyOptions::default().header,
+        };
+        let Some(key) = request.headers().get_one(&header) else {
+            return fail(request, Error::MissingCredentials);
+        };
+        // verifying the secret runs argon2, keep it off the executor
+        let (api_keys, key) = (api_keys.clone(), key.to_string());
+        let entry = match blocking(move || Ok(api_keys.verify_api_key(&key)))
+            .await
+            .and_then(|verified| verified)
+        {
+            Ok(Some(entry)) => entry,
+            Ok(None) => return fail(request, Error::InvalidCredentials),
+            Err(e) if e.status() == Status::InternalServerError => {
+                error!("{e}");
+                return Outcome::Forward(Status::InternalServerError);
+            }
+            Err(e) => return fail(request, e),
+        };
+        let passport = match register.passport(&entry.passport_id).await {
             Err(e) => {
                 error!("{e}");
                 return Outcome::Forward(Status::InternalServerError);
             }
-            Ok(u) => u,
+            Ok(None) => {
+                return fail(
+                    request,
+                    Error::PassportNotFound(entry.passport_id),
+                )
+            }
+            Ok(Some(p)) => p,
         };
-        match user {
-            None => Outcome::Error((
-                Status::Unauthorized,
-                anyhow!("User not found."),
-            )),
-            Some(u) => Outcome::Success(u),
+        if let Err(e) = register.status_policy().check(&passport) {
+            return fail(request, e.into());
         }
+        let now = Utc::now();
+        let exp = entry.expires_at.unwrap_or(DateTime::<Utc>::MAX_UTC);
+        let mut data = JsonWebToken::for_subject(
+            &entry.passport_id,
+            &entry.id,
+            now.timestamp() as usize,
+            exp.timestamp() as usize,
+        );
+        data.services = Some(entry.services);
+        check_revocation(request, BoardingPass::new(data))
+    }
+}
+
+/// Aborts launch if a route requires this guard without a managed `PR`.
+impl<PR> Sentinel for BoardingPass<JsonWebToken, Basic<PR>>
+where
+    PR: AsyncPassportRegister + 'static,
+{
+    fn abort(rocket: &Rocket<Ignite>) -> bool {
+        if rocket.state::<PR>().is_some() {
+            return false;
+        }
+        error!(
+            "A route requires a basic BoardingPass, but no cosmodrome \
+             PassportRegister of type {} is managed by rocket.",
+            std::any::type_name::<PR>()
+        );
+        true
     }
 }
 
 #[rocket::async_trait]
-impl<'r> FromRequest<'r> for BoardingPass<JsonWebToken, Bearer> {
-    type Error = anyhow::Error;
+impl<'r, PR> FromRequest<'r> for BoardingPass<JsonWebToken, Basic<PR>>
+where
+    PR: AsyncPassportRegister + 'static,
+{
+    type Error = Error;
 
     async fn from_request(
         request: &'r Request<'_>,
     ) -> Outcome<Self, Self::Error> {
-        let Some(cipher) = request.rocket().state::<JwtCipher>() else {
-            log::error!(
-                "No JwtCipher managed by rocket. Please create an instance \
-                 and manage it with rocket."
+        let Some(register) = request.rocket().state::<PR>() else {
+            error!(
+                "No cosmodrome PassportRegister managed by rocket. Please \
+                 create an instance and manage it with rocket."
             );
             return Outcome::Forward(Status::InternalServerError);
         };
-        let headers = request.headers();
-
-        let Some(auth) = headers.get_one("Authorization") else {
-            return Outcome::Error((
-                Status::Unauthorized,
-                anyhow!("No Authorization header available."),
-            ));
+        let reject = |e: Error| {
+            let realm = match request.rocket().state::<BasicOptions>() {
+                Some(o) => o.realm.clone(),
+                None => BasicOptions::default().realm,
+            };
+            challenge(
+                request,
+                format!("Basic realm=\"{realm}\", charset=\"UTF-8\""),
+            );
+            fail(request, e)
         };
-        let Some(user) = Bearer::extract_value(auth, None) else {
-            return Outcome::Error((
-                Status::Unauthorized,
-                anyhow!("Not a valid Bearer authorization header."),
-            ));
+        let Some(ticket) = request
+            .headers()
+            .get_one("Authorization")
+            .and_then(Basic::extract_ticket)
+        else {
+            return reject(Error::MissingCredentials);
         };
-        let user = cipher.decode(&user);
-        match user {
-            Err(e) => {
-                return Outcome::Error((Status::Unauthorized, anyhow!("{e}")));
+        let passport = match register.verify_credentials(&ticket).await {
+            Err(e) if e.status() == Status::InternalServerError => {
+                error!("{e}");
+                return Outcome::Forward(Status::InternalServerError);
+            }
+            Err(e) => return reject(e),
+            Ok(None) => return reject(Error::InvalidCredentials),
+            Ok(Some(p)) => p,
+        };
+        if let Err(e) = register.status_policy().check(&passport) {
+            return fail(request, e.into());
+        }
+        let projection = ClaimsProjection {
+            role: true,
+            services: true,
+        };
+        let data = JsonWebToken::new(
+            &passport,
+            TokenLifetime::default().lifetime(&passport),
+            &ValidationOptions::default(),
+        )
+        .project(&passport, &projection);
+        Outcome::Success(BoardingPass::new(data))
+    }
+}
+
+/// Aborts launch if a route requires this guard without a managed cipher.
+impl Sentinel for BoardingPass<JsonWebToken, CookieOrBearer> {
+    fn abort(rocket: &Rocket<Ignite>) -> bool {
+        abort_without_cipher(rocket)
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for BoardingPass<JsonWebToken, CookieOrBearer> {
+    type Error = Error;
+
+    async fn from_request(
+        request: &'r Request<'_>,
+    ) -> Outcome<Self, Self::Error> {
+        let order = match request.rocket().state::<CookieOrBearerOptions>() {
+            Some(options) => options.order,
+            None => CookieOrBearerOptions::default().order,
+        };
+        let mut authenticated: Option<(AuthSource, Self)> = None;
+        for source in order {
+            if !has_source(request, source) {
+                continue;
+            }
+            let outcome = match source {
+                AuthSource::Cookie => request
+                    .guard::<BoardingPass<JsonWebToken, Cookie>>()
+                    .await
+                    .map(|b| BoardingPass::new(b.data)),
+                AuthSource::Bearer => request
+                    .guard::<BoardingPass<JsonWebToken, Bearer>>()
+                    .await
+                    .map(|b| BoardingPass::new(b.data)),
+            };
+            match (&authenticated, outcome) {
+                (None, Outcome::Success(b)) => {
+                    authenticated = Some((source, b))
+                }
+                (None, Outcome::Error(e)) => return Outcome::Error(e),
+                (None, Outcome::Forward(s)) => return Outcome::Forward(s),
+                (Some((_, first)), Outcome::Success(b))
+                    if first.data.sub == b.data.sub => {}
+                (Some(_), _) => {
+                    return fail(
+                        request,
+                        Error::InvalidToken(
+                            "Conflicting credentials.".to_string(),
+                        ),
+                    )
+                }
             }
-            Ok(u) => Outcome::Success(u),
         }
+        let Some((source, boarding_pass)) = authenticated else {
+            return fail(request, Error::MissingCredentials);
+        };
+        request.local_cache(|| Some(source));
+        Outcome::Success(boarding_pass)
+    }
+}
+
+/// Returns the [AuthSource] that authenticated the
+/// [BoardingPass<JsonWebToken, CookieOrBearer>].
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AuthSource {
+    type Error = Error;
+
+    async fn from_request(
+        request: &'r Request<'_>,
+    ) -> Outcome<Self, Self::Error> {
+        match request
+            .guard::<BoardingPass<JsonWebToken, CookieOrBearer>>()
+            .await
+        {
+            Outcome::Success(_) => (),
+            Outcome::Error(e) => return Outcome::Error(e),
+            Outcome::Forward(s) => return Outcome::Forward(s),
+        };
+        match request.local_cache(|| None::<AuthSource>) {
+            Some(source) => Outcome::Success(*source),
+            None => Outcome::Forward(Status::InternalServerError),
+        }
+    }
+}
+
+/// Returns `true` if the request carries credentials of the given source.
+fn has_source(request: &Request<'_>, source: AuthSource) -> bool {
+    match source {
+        AuthSource::Cookie => request
+            .cookies()
+            .get_private(&cookie_name(request))
+            .is_some(),
+        AuthSource::Bearer => matches!(
+            bearer_token(request, &bearer_options(request)),
+            BearerToken::Present(_)
+        ),
+    }
+}
+
+/// The result of looking up the token for the [Bearer] [AuthType].
+enum BearerToken {
+    /// The request does not contain a token, or only credentials of another
+    /// scheme.
+    Missing,
+    /// The header uses the bearer scheme, but does not contain a valid token.
+    Malformed,
+    /// The token that has been found.
+    Present(String),
+}
+
+/// Returns the managed [BearerOptions], or the default ones.
+fn bearer_options(request: &Request<'_>) -> BearerOptions {
+    match request.rocket().state::<BearerOptions>() {
+        Some(options) => options.clone(),
+        None => BearerOptions::default(),
+    }
+}
+
+/// Looks up the token in the header, followed by the query parameter and the
+/// `Sec-WebSocket-Protocol` header if configured.
+fn bearer_token(request: &Request<'_>, options: &BearerOptions) -> BearerToken {
+    if let Some(value) = request.headers().get_one(&options.header) {
+        // Credentials of another scheme, eg. `Basic`, are no bearer token.
+        let scheme = value.split_once(' ').map_or(value, |(s, _)| s);
+        if scheme.eq_ignore_ascii_case(&options.scheme) {
+            return match options.extract_value(value) {
+                Some(token) => BearerToken::Present(token),
+                None => BearerToken::Malformed,
+            };
+        }
+    }
+    if let Some(parameter) = &options.query_parameter {
+        if let Some(Ok(value)) = request.query_value::<&str>(parameter) {
+            return match options.strip_prefix(value) {
+                Some(token) => BearerToken::Present(token),
+                None => BearerToken::Malformed,
+            };
+        }
+    }
+    if let Some(value) = request.headers().get_one("Sec-WebSocket-Protocol") {
+        if let Some(token) = options.extract_websocket_value(value) {
+            return BearerToken::Present(token);
+        }
+    }
+    BearerToken::Missing
+}
+
+/// Returns the name of the cookie from the managed [CookieStorageOptions], or
+/// the default one.
+fn cookie_name(request: &Request<'_>) -> String {
+    match request.rocket().state::<CookieStorageOptions>() {
+        Some(options) => options.cookie_template.name().to_string(),
+        None => CookieStorageOptions::default()
+            .cookie_template
+            .name()
+            .to_string(),
+    }
+}
+
+/// Checks if a cipher is managed by [rocket] and logs an error if not.
+fn is_cipher_managed(request: &Request<'_>) -> bool {
+    if is_managed(request.rocket()) {
+        return true;
+    }
+    error!(
+        "No cosmodrome JwtCipher, JwtKeyring or JwksVerifier managed by \
+         rocket. Please create an instance and manage it with rocket."
+    );
+    false
+}
+
+/// Rejects the given [BoardingPass] if it is contained in the managed
+/// [RevocationList].
+fn check_revocation<AT: AuthType>(
+    request: &Request<'_>,
+    boarding_pass: BoardingPass<JsonWebToken, AT>,
+) -> Outcome<BoardingPass<JsonWebToken, AT>, Error> {
+    let Some(revocations) = request.rocket().state::<RevocationList>() else {
+        return Outcome::Success(boarding_pass);
+    };
+    match revocations.is_revoked(&boarding_pass.data) {
+        Err(e) => {
+            error!("{e}");
+            Outcome::Forward(Status::InternalServerError)
+        }
+        Ok(true) => fail(request, Error::TokenRevoked),
+        Ok(false) => Outcome::Success(boarding_pass),
+    }
+}
+
+/// Returns `true` and logs an error if no cipher is managed by [rocket].
+fn abort_without_cipher(rocket: &Rocket<Ignite>) -> bool {
+    if is_managed(rocket) {
+        return false;
+    }
+    error!(
+        "A route requires a BoardingPass, but no cosmodrome JwtCipher, \
+         JwtKeyring or JwksVerifier is managed by rocket."
+    );
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::{
+        api_key::MemoryApiKeyRegister,
+        passport::{
+            Passport,
+            PassportType,
+        },
+        passport_register::{
+            MemoryPassportRegister,
+            PassportRegister,
+        },
+        session::MemorySessionBackend,
+    };
+    use rocket::{
+        http::Cookie as RocketCookie,
+        local::blocking::Client,
+    };
+
+    /// A backend that does not filter expired sessions.
+    struct UnfilteredBackend(JsonWebToken);
+
+    impl SessionBackend<JsonWebToken> for UnfilteredBackend {
+        fn session(
+            &self,
+            _session_id: &str,
+        ) -> Result<Option<JsonWebToken>, Error> {
+            Ok(Some(self.0.clone()))
+        }
+        fn store_session(
+            &self,
+            _session_id: &str,
+            _data: JsonWebToken,
+        ) -> Result<(), Error> {
+            Ok(())
+        }
+        fn remove_session(&self, _session_id: &str) -> Result<(), Error> {
+            Ok(())
+        }
+    }
+
+    #[rocket::get("/")]
+    fn session(boarding_pass: BoardingPass<JsonWebToken, Session>) -> String {
+        boarding_pass.data.sub
+    }
+
+    fn status(sessions: SessionStore) -> Status {
+        let rocket = rocket::build()
+            .manage(sessions)
+            .mount("/", rocket::routes![session]);
+        let client = Client::tracked(rocket).unwrap();
+        let cookie = RocketCookie::new(
+            CookieStorageOptions::default()
+                .cookie_template
+                .name()
+                .to_string(),
+            "session",
+        );
+        let status = client.get("/").private_cookie(cookie).dispatch().status();
+        status
+    }
+
+    #[test]
+    fn session_guard_rejects_expired_sessions() {
+        let now = Utc::now().timestamp();
+        let valid = JsonWebToken::for_subject(
+            "cosmo",
+            "session",
+            now as usize,
+            (now + 60) as usize,
+        );
+        let expired = JsonWebToken::for_subject(
+            "cosmo",
+            "session",
+            (now - 120) as usize,
+            (now - 60) as usize,
+        );
+        let backend = UnfilteredBackend(valid);
+        assert_eq!(status(SessionStore::new(backend)), Status::Ok);
+        let backend = UnfilteredBackend(expired);
+        assert_eq!(status(SessionStore::new(backend)), Status::Unauthorized);
+        let backend = MemorySessionBackend::default();
+        assert_eq!(status(SessionStore::new(backend)), Status::Unauthorized);
+    }
+
+    #[rocket::get("/")]
+    fn api_key(boarding_pass: BoardingPass<JsonWebToken, ApiKey>) -> String {
+        boarding_pass.data.sub
+    }
+
+    #[test]
+    fn api_key_guard_checks_owning_passport() {
+        let register = MemoryPassportRegister::default();
+        let api_keys = ApiKeyStore::new(MemoryApiKeyRegister::default());
+        let mut keys = vec![];
+        for (id, disabled) in [("cosmo", false), ("nova", true)] {
+            let passport = Passport::from_parts(
+                id,
+                "",
+                vec![],
+                PassportType::User,
+                disabled,
+                true,
+                DateTime::<Utc>::MAX_UTC,
+            );
+            keys.push(
+                api_keys.issue_api_key("cd", &passport, &[], None).unwrap(),
+            );
+            PassportRegister::set_passport(&register, passport).unwrap();
+        }
+        let rocket = rocket::build()
+            .manage(register)
+            .manage(api_keys)
+            .mount("/", rocket::routes![api_key]);
+        let client = Client::tracked(rocket).unwrap();
+        let status = |key: &str| {
+            let header =
+                rocket::http::Header::new("X-API-Key", key.to_string());
+            client.get("/").header(header).dispatch().status()
+        };
+        assert_eq!(status(&keys[0]), Status::Ok);
+        assert_eq!(status(&keys[1]), Status::Forbidden);
+        assert_eq!(status("cd_unknown_secret"), Status::Unauthorized);
+    }
+
+    #[test]
+    fn bearer_token_falls_back_to_query_and_websocket() {
+        let options = BearerOptions {
+            query_parameter: Some("access_token".to_string()),
+            websocket_protocol: Some("access_token".to_string()),
+            ..Default::default()
+        };
+        let client = Client::untracked(rocket::build()).unwrap();
+        let token = |request: rocket::local::blocking::LocalRequest<'_>| {
+            match bearer_token(request.inner(), &options) {
+                BearerToken::Missing => "missing".to_string(),
+                BearerToken::Malformed => "malformed".to_string(),
+                BearerToken::Present(token) => token,
+            }
+        };
+        let header = |name: &'static str, value: &'static str| {
+            rocket::http::Header::new(name, value)
+        };
+        assert_eq!(
+            token(client.get("/").header(header("Authorization", "Bearer a"))),
+            "a"
+        );
+        assert_eq!(
+            token(client.get("/").header(header("Authorization", "Bearer"))),
+            "malformed"
+        );
+        assert_eq!(
+            token(client.get("/").header(header("Authorization", "Basic a"))),
+            "missing"
+        );
+        assert_eq!(
+            token(
+                client
+                    .get("/?access_token=b")
+                    .header(header("Authorization", "Basic a"))
+            ),
+            "b"
+        );
+        assert_eq!(
+            token(
+                client.get("/").header(header(
+                    "Sec-WebSocket-Protocol",
+                    "access_token, c"
+                ))
+            ),
+            "c"
+        );
+        assert_eq!(token(client.get("/")), "missing");
+        let options = BearerOptions::default();
+        let request = client.get("/?access_token=b");
+        assert!(matches!(
+            bearer_token(request.inner(), &options),
+            BearerToken::Missing
+        ));
     }
 }
@@ -1,5 +1,11 @@
 //! Different data types that can be used as payload in a [BoardingPass](super::BoardingPass).
-use super::super::passport::Passport;
+use super::super::{
+    ciphering::ValidationOptions,
+    passport::{
+        Passport,
+        PassportType,
+    },
+};
 use chrono::{
     TimeDelta,
     Utc,
@@ -10,26 +16,135 @@ use rocket::serde::{
 };
 
 /// Defines the content of a [jsonwebtoken], also referred to as `claim`.
-#[derive(Serialize, Deserialize, Debug)]
+///
+/// Only the registered claims are contained by default. The [Passport] itself
+/// is never part of the token, it is referenced by [JsonWebToken::sub]
+/// instead. [PassportType] and services can be added by using a
+/// [ClaimsProjection].
+#[derive(Serialize, Deserialize, Clone, Debug)]
 #[serde(crate = "rocket::serde")]
 pub struct JsonWebToken {
-    /// The user passport.
-    pub passport: Passport,
+    /// The subject of the token, equals the [id](Passport::id) of the passport.
+    pub sub: String,
+    /// Unique identifier of this token.
+    pub jti: String,
+    /// Timestamp when the token has been issued.
+    pub iat: usize,
+    /// Timestamp before which the token must not be accepted.
+    pub nbf: usize,
     exp: usize,
+    /// The issuer of the token.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iss: Option<String>,
+    /// The audiences the token is intended for.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aud: Option<Vec<String>>,
+    /// The [PassportType] of the passport, if projected.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub role: Option<PassportType>,
+    /// The services of the passport, if projected.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub services: Option<Vec<String>>,
 }
 
 impl JsonWebToken {
-    /// Creates a new claim from the given values.
-    pub fn new(passport: &Passport, valid_timespan: TimeDelta) -> Self {
-        let exp = Utc::now() + valid_timespan;
+    /// Creates a new claim from the given values. `iss` and `aud` are taken
+    /// from the given [ValidationOptions] so that the token is accepted by a
+    /// cipher using the same options.
+    pub fn new(
+        passport: &Passport,
+        valid_timespan: TimeDelta,
+        validation_options: &ValidationOptions,
+    ) -> Self {
+        let now = Utc::now();
+        let exp = now + valid_timespan;
         Self {
-            passport: passport.to_owned(),
+            sub: passport.id.clone(),
+            jti: Self::generate_jti(),
+            iat: now.timestamp() as usize,
+            nbf: now.timestamp() as usize,
             exp: exp.timestamp() as usize,
+            iss: validation_options.issuer.clone(),
+            aud: match validation_options.audiences.is_empty() {
+                true => None,
+                false => Some(validation_options.audiences.clone()),
+            },
+            role: None,
+            services: None,
+        }
+    }
+
+    /// Creates a claim for the given subject that has not been issued by a
+    /// cipher, eg. for an API key.
+    pub(crate) fn for_subject(
+        sub: &str,
+        jti: &str,
+        iat: usize,
+        exp: usize,
+    ) -> Self {
+        Self {
+            sub: sub.to_string(),
+            jti: jti.to_string(),
+            iat,
+            nbf: iat,
+            exp,
+            iss: None,
+            aud: None,
+            role: None,
+            services: None,
+        }
+    }
+
+    /// Adds the values selected by the given [ClaimsProjection] to the claim.
+    pub fn project(
+        mut self,
+        passport: &Passport,
+        projection: &ClaimsProjection,
+    ) -> Self {
+        if projection.role {
+            self.role = Some(passport.account_type.clone());
         }
+        if projection.services {
+            self.services = Some(passport.services().to_vec());
+        }
+        self
+    }
+
+    /// Returns the timestamp when this token expires.
+    pub fn exp(&self) -> usize {
+        self.exp
     }
 
     /// Returns `true` if the token is still valid.
     pub fn is_valid(&self) -> bool {
         self.exp > Utc::now().timestamp() as usize
     }
+
+    /// Generates a random token identifier.
+    fn generate_jti() -> String {
+        use rand::{
+            distributions::Alphanumeric,
+            thread_rng,
+            Rng,
+        };
+
+        thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(32)
+            .map(char::from)
+            .collect()
+    }
+}
+
+/// Selects the [Passport] values that are copied into a [JsonWebToken].
+///
+/// Everything contained in a token is readable by its holder, so nothing
+/// is projected by default.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(crate = "rocket::serde", default)]
+pub struct ClaimsProjection {
+    /// Copies [Passport::account_type] to [JsonWebToken::role].
+    pub role: bool,
+    /// Copies [Passport::services] to [JsonWebToken::services].
+    pub services: bool,
 }
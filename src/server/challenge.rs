@@ -0,0 +1,67 @@
+//! `WWW-Authenticate` challenges for rejected requests.
+//!
+//! Request guards can not set response headers, so they store their challenge
+//! in the request and the [WwwAuthenticate] fairing adds it to the
+//! `401 Unauthorized` response.
+//!
+//! ```rust,ignore
+//! rocket::build()
+//!     .attach(WwwAuthenticate)
+//! ```
+use rocket::{
+    fairing::{
+        Fairing,
+        Info,
+        Kind,
+    },
+    http::{
+        Header,
+        Status,
+    },
+    Request,
+    Response,
+};
+
+/// The challenge stored by a request guard.
+struct Challenge(String);
+
+/// Fairing that adds the challenge of the rejecting request guard as
+/// `WWW-Authenticate` header to `401 Unauthorized` responses.
+///
+/// An existing `WWW-Authenticate` header is not replaced.
+#[derive(Debug, Default)]
+pub struct WwwAuthenticate;
+
+#[rocket::async_trait]
+impl Fairing for WwwAuthenticate {
+    fn info(&self) -> Info {
+        Info {
+            name: "cosmodrome WWW-Authenticate",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(
+        &self,
+        request: &'r Request<'_>,
+        response: &mut Response<'r>,
+    ) {
+        if response.status() != Status::Unauthorized
+            || response.headers().contains("WWW-Authenticate")
+        {
+            return;
+        }
+        if let Some(Challenge(challenge)) =
+            request.local_cache(|| None::<Challenge>)
+        {
+            response
+                .set_header(Header::new("WWW-Authenticate", challenge.clone()));
+        }
+    }
+}
+
+/// Stores the given challenge for the [WwwAuthenticate] fairing. Only the
+/// first challenge of a request is kept.
+pub(crate) fn challenge(request: &Request<'_>, challenge: String) {
+    request.local_cache(|| Some(Challenge(challenge)));
+}
@@ -2,17 +2,51 @@
 use super::{
     auth_type::AuthType,
     boarding_pass::{
-        payloads::JsonWebToken,
+        payloads::{
+            ClaimsProjection,
+            JsonWebToken,
+        },
         BoardingPass,
     },
+    error::Error,
+    jwks::{
+        public_jwk,
+        JwksVerifier,
+    },
+    passport::{
+        Passport,
+        PassportType,
+    },
 };
 use anyhow::anyhow;
+use chrono::{
+    TimeDelta,
+    Utc,
+};
 use jsonwebtoken::{
+    jwk::{
+        Jwk,
+        JwkSet,
+    },
+    Algorithm,
     DecodingKey,
     EncodingKey,
     Header,
     Validation,
 };
+pub use keyring::JwtKeyring;
+use rocket::{
+    serde::Deserialize,
+    Orbit,
+    Phase,
+    Rocket,
+};
+use std::{
+    collections::HashMap,
+    path::Path,
+};
+
+mod keyring;
 
 /// Methods for encoding and decoding a [BoardingPass].
 pub trait Ciphering<BPD, AT, CE>
@@ -23,23 +57,242 @@ where
     fn encode(
         &self,
         boarding_pass: &BoardingPass<BPD, AT>,
-    ) -> anyhow::Result<CE>;
+    ) -> Result<CE, Error>;
     /// Decodoes the given [BoardingPass].
     fn decode(
         &self,
         encoded_value: &CE,
-    ) -> anyhow::Result<BoardingPass<BPD, AT>>;
+    ) -> Result<BoardingPass<BPD, AT>, Error>;
+}
+
+/// Issues new [BoardingPass]es.
+pub trait Issuing<BPD, AT>
+where
+    AT: AuthType,
+{
+    /// Issues a new [BoardingPass] for the given [Passport].
+    fn issue(&self, passport: &Passport) -> BoardingPass<BPD, AT>;
+}
+
+/// Returns `true` if a [JwtCipher], [JwtKeyring] or [JwksVerifier] is
+/// managed by [rocket].
+pub(crate) fn is_managed<P: Phase>(rocket: &Rocket<P>) -> bool {
+    rocket.state::<JwtCipher>().is_some()
+        || rocket.state::<JwtKeyring>().is_some()
+        || rocket.state::<JwksVerifier>().is_some()
+}
+
+/// Decodes the given value with the cipher that is managed by [rocket].
+///
+/// Returns `None` if neither a [JwtCipher], [JwtKeyring] nor a [JwksVerifier]
+/// is managed.
+pub(crate) fn decode_managed<AT>(
+    rocket: &Rocket<Orbit>,
+    encoded_value: &String,
+) -> Option<Result<BoardingPass<JsonWebToken, AT>, Error>>
+where
+    AT: AuthType,
+{
+    if let Some(cipher) = rocket.state::<JwtCipher>() {
+        return Some(cipher.decode(encoded_value));
+    }
+    if let Some(keyring) = rocket.state::<JwtKeyring>() {
+        return Some(keyring.decode(encoded_value));
+    }
+    rocket
+        .state::<JwksVerifier>()
+        .map(|verifier| verifier.decode(encoded_value))
+}
+
+/// Options that are used to validate the claims of a [JsonWebToken].
+#[derive(Clone, Debug)]
+pub struct ValidationOptions {
+    /// If set, only tokens issued by this issuer are accepted and the `iss`
+    /// claim is required. Also used as `iss` claim of issued tokens.
+    pub issuer: Option<String>,
+    /// If not empty, only tokens containing at least one of these audiences
+    /// are accepted and the `aud` claim is required. Also used as `aud` claim
+    /// of issued tokens.
+    pub audiences: Vec<String>,
+    /// Leeway in seconds that is applied to `exp`, `nbf` and the maximum age.
+    pub leeway: u64,
+    /// Whether the `nbf` claim is validated.
+    pub validate_nbf: bool,
+    /// Claims that need to be present in the token. Supported values are
+    /// `exp`, `nbf`, `aud`, `iss` and `sub`.
+    pub required_claims: Vec<String>,
+    /// If set, tokens that have been issued longer ago than this are rejected,
+    /// independent of their `exp` claim.
+    pub max_age: Option<TimeDelta>,
+}
+
+impl Default for ValidationOptions {
+    /// Default implementation:
+    ///
+    /// - Issuer: not validated
+    /// - Audiences: not validated
+    /// - Leeway: `60` seconds
+    /// - Validate nbf: `true`
+    /// - Required claims: `exp`, `nbf` and `sub`
+    /// - Max age: not validated
+    fn default() -> Self {
+        Self {
+            issuer: None,
+            audiences: vec![],
+            leeway: 60,
+            validate_nbf: true,
+            required_claims: vec![
+                "exp".to_string(),
+                "nbf".to_string(),
+                "sub".to_string(),
+            ],
+            max_age: None,
+        }
+    }
+}
+
+impl ValidationOptions {
+    /// Creates the [Validation] for the given [Algorithm].
+    pub(crate) fn validation(&self, algorithm: Algorithm) -> Validation {
+        let mut validation = Validation::new(algorithm);
+        validation.leeway = self.leeway;
+        validation.validate_nbf = self.validate_nbf;
+        let mut required_claims = self.required_claims.clone();
+        if let Some(issuer) = &self.issuer {
+            validation.set_issuer(&[issuer]);
+            required_claims.push("iss".to_string());
+        }
+        if !self.audiences.is_empty() {
+            validation.set_audience(&self.audiences);
+            required_claims.push("aud".to_string());
+        }
+        validation.set_required_spec_claims(&required_claims);
+        validation
+    }
+
+    /// Validates the claims that are not covered by [Validation].
+    pub(crate) fn validate(&self, claims: &JsonWebToken) -> Result<(), Error> {
+        if let Some(max_age) = self.max_age {
+            let oldest = Utc::now() - max_age;
+            if (claims.iat as i64) + (self.leeway as i64) < oldest.timestamp() {
+                return Err(Error::InvalidToken(
+                    "Token exceeds the maximum age.".to_string(),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Determines the lifetime of issued tokens, optionally depending on the
+/// [PassportType] of the [Passport].
+///
+/// ```rust,ignore
+/// // admins get 15 minutes, everyone else one week
+/// let lifetime = TokenLifetime::default()
+///     .with_override(PassportType::Admin, TimeDelta::minutes(15));
+/// ```
+#[derive(Clone, Debug)]
+pub struct TokenLifetime {
+    /// The lifetime of tokens whose [PassportType] has no override.
+    pub default: TimeDelta,
+    /// Lifetimes for specific [PassportType]s.
+    pub overrides: HashMap<PassportType, TimeDelta>,
+}
+
+impl Default for TokenLifetime {
+    /// Default implementation:
+    ///
+    /// - Default: `1 week`
+    /// - Overrides: none
+    fn default() -> Self {
+        Self::new(TimeDelta::weeks(1))
+    }
+}
+
+impl TokenLifetime {
+    /// Creates a new policy with the given lifetime for all [PassportType]s.
+    pub fn new(default: TimeDelta) -> Self {
+        Self {
+            default,
+            overrides: HashMap::new(),
+        }
+    }
+
+    /// Sets the lifetime for the given [PassportType].
+    pub fn with_override(
+        mut self,
+        passport_type: PassportType,
+        lifetime: TimeDelta,
+    ) -> Self {
+        self.overrides.insert(passport_type, lifetime);
+        self
+    }
+
+    /// Returns the lifetime of a token issued for the given [Passport].
+    pub fn lifetime(&self, passport: &Passport) -> TimeDelta {
+        self.overrides
+            .get(&passport.account_type)
+            .copied()
+            .unwrap_or(self.default)
+    }
+}
+
+/// The encoding of the key material that is passed to a [JwtCipher].
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Deserialize)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+pub enum KeyFormat {
+    /// PEM encoded keys.
+    Pem,
+    /// DER encoded keys.
+    Der,
+}
+
+/// The key families supported by [jsonwebtoken].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum KeyFamily {
+    Hmac,
+    Rsa,
+    Ec,
+    Ed,
+}
+
+impl From<Algorithm> for KeyFamily {
+    fn from(value: Algorithm) -> Self {
+        match value {
+            Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512 => {
+                Self::Hmac
+            }
+            Algorithm::RS256
+            | Algorithm::RS384
+            | Algorithm::RS512
+            | Algorithm::PS256
+            | Algorithm::PS384
+            | Algorithm::PS512 => Self::Rsa,
+            Algorithm::ES256 | Algorithm::ES384 => Self::Ec,
+            Algorithm::EdDSA => Self::Ed,
+        }
+    }
 }
 
 /// Required to en- and decode a [BoardingPass] that contains a [JsonWebToken] as payload.
+///
+/// A cipher that has been created by one of the `verifier` functions only
+/// holds the public key and is therefore only able to decode.
 #[derive(Clone)]
 pub struct JwtCipher {
-    enc_key: EncodingKey,
+    algorithm: Algorithm,
+    enc_key: Option<EncodingKey>,
     dec_key: DecodingKey,
+    kid: Option<String>,
+    public_jwk: Option<Jwk>,
+    claims_projection: ClaimsProjection,
+    validation_options: ValidationOptions,
+    token_lifetime: TokenLifetime,
 }
 
 impl JwtCipher {
-    /// Creates a random cipher.
+    /// Creates a random cipher using [Algorithm::HS256].
     pub fn random() -> Self {
         use rand::{
             distributions::Alphanumeric,
@@ -53,10 +306,262 @@ impl JwtCipher {
             .map(char::from)
             .collect();
         Self {
-            enc_key: EncodingKey::from_secret(authentication_secret.as_bytes()),
+            algorithm: Algorithm::HS256,
+            enc_key: Some(EncodingKey::from_secret(
+                authentication_secret.as_bytes(),
+            )),
             dec_key: DecodingKey::from_secret(authentication_secret.as_bytes()),
+            kid: None,
+            public_jwk: None,
+            claims_projection: ClaimsProjection::default(),
+            validation_options: ValidationOptions::default(),
+            token_lifetime: TokenLifetime::default(),
+        }
+    }
+
+    /// Creates a cipher from the given secret. The `algorithm` needs to be one
+    /// of the `HS` family.
+    pub fn from_secret(
+        algorithm: Algorithm,
+        secret: &[u8],
+    ) -> anyhow::Result<Self> {
+        if KeyFamily::from(algorithm) != KeyFamily::Hmac {
+            return Err(anyhow!(
+                "Algorithm {algorithm:?} can not be used with a secret."
+            ));
+        }
+        Ok(Self {
+            algorithm,
+            enc_key: Some(EncodingKey::from_secret(secret)),
+            dec_key: DecodingKey::from_secret(secret),
+            kid: None,
+            public_jwk: None,
+            claims_projection: ClaimsProjection::default(),
+            validation_options: ValidationOptions::default(),
+            token_lifetime: TokenLifetime::default(),
+        })
+    }
+
+    /// Creates a cipher from the given RSA, ECDSA or Ed25519 key pair. The key
+    /// type has to match the given `algorithm`.
+    pub fn from_key_pair(
+        algorithm: Algorithm,
+        format: KeyFormat,
+        private_key: &[u8],
+        public_key: &[u8],
+    ) -> anyhow::Result<Self> {
+        let family = Self::asymmetric_family(algorithm)?;
+        let enc_key = match (family, format) {
+            (KeyFamily::Rsa, KeyFormat::Pem) => {
+                EncodingKey::from_rsa_pem(private_key)?
+            }
+            (KeyFamily::Rsa, KeyFormat::Der) => {
+                EncodingKey::from_rsa_der(private_key)
+            }
+            (KeyFamily::Ec, KeyFormat::Pem) => {
+                EncodingKey::from_ec_pem(private_key)?
+            }
+            (KeyFamily::Ec, KeyFormat::Der) => {
+                EncodingKey::from_ec_der(private_key)
+            }
+            (KeyFamily::Ed, KeyFormat::Pem) => {
+                EncodingKey::from_ed_pem(private_key)?
+            }
+            (KeyFamily::Ed, KeyFormat::Der) => {
+                EncodingKey::from_ed_der(private_key)
+            }
+            (KeyFamily::Hmac, _) => unreachable!(),
+        };
+        let mut cipher = Self::verifier(algorithm, format, public_key)?;
+        cipher.enc_key = Some(enc_key);
+        Ok(cipher)
+    }
+
+    /// Same as [JwtCipher::from_key_pair], but reads the keys from the given
+    /// files.
+    pub fn from_key_files(
+        algorithm: Algorithm,
+        format: KeyFormat,
+        private_key: impl AsRef<Path>,
+        public_key: impl AsRef<Path>,
+    ) -> anyhow::Result<Self> {
+        Self::from_key_pair(
+            algorithm,
+            format,
+            &Self::read_key(private_key)?,
+            &Self::read_key(public_key)?,
+        )
+    }
+
+    /// Creates a verify only cipher from the given public key. The key type
+    /// has to match the given `algorithm`.
+    pub fn verifier(
+        algorithm: Algorithm,
+        format: KeyFormat,
+        public_key: &[u8],
+    ) -> anyhow::Result<Self> {
+        let family = Self::asymmetric_family(algorithm)?;
+        let dec_key = match (family, format) {
+            (KeyFamily::Rsa, KeyFormat::Pem) => {
+                DecodingKey::from_rsa_pem(public_key)?
+            }
+            (KeyFamily::Rsa, KeyFormat::Der) => {
+                DecodingKey::from_rsa_der(public_key)
+            }
+            (KeyFamily::Ec, KeyFormat::Pem) => {
+                DecodingKey::from_ec_pem(public_key)?
+            }
+            (KeyFamily::Ec, KeyFormat::Der) => {
+                DecodingKey::from_ec_der(public_key)
+            }
+            (KeyFamily::Ed, KeyFormat::Pem) => {
+                DecodingKey::from_ed_pem(public_key)?
+            }
+            (KeyFamily::Ed, KeyFormat::Der) => {
+                DecodingKey::from_ed_der(public_key)
+            }
+            (KeyFamily::Hmac, _) => unreachable!(),
+        };
+        Ok(Self {
+            algorithm,
+            enc_key: None,
+            dec_key,
+            kid: None,
+            public_jwk: Some(public_jwk(algorithm, format, public_key)?),
+            claims_projection: ClaimsProjection::default(),
+            validation_options: ValidationOptions::default(),
+            token_lifetime: TokenLifetime::default(),
+        })
+    }
+
+    /// Same as [JwtCipher::verifier], but reads the key from the given file.
+    pub fn verifier_from_file(
+        algorithm: Algorithm,
+        format: KeyFormat,
+        public_key: impl AsRef<Path>,
+    ) -> anyhow::Result<Self> {
+        Self::verifier(algorithm, format, &Self::read_key(public_key)?)
+    }
+
+    /// Returns the [Algorithm] that is used for signing and verification.
+    pub fn algorithm(&self) -> Algorithm {
+        self.algorithm
+    }
+
+    /// Sets the key id that is stamped into the `kid` header of every token.
+    pub fn with_kid(mut self, kid: &str) -> Self {
+        self.kid = Some(kid.to_string());
+        self
+    }
+
+    /// Returns the key id of this cipher, if set.
+    pub fn kid(&self) -> Option<&str> {
+        self.kid.as_deref()
+    }
+
+    /// Returns the public key of this cipher as [Jwk]. Ciphers using a secret
+    /// do not have a public key.
+    pub fn jwk(&self) -> Option<Jwk> {
+        let mut jwk = self.public_jwk.clone()?;
+        jwk.common.key_id.clone_from(&self.kid);
+        Some(jwk)
+    }
+
+    /// Returns a [JwkSet] containing the public key of this cipher. Use
+    /// [JwtCipher::with_kid] when the set is consumed by a [JwksVerifier].
+    pub fn jwk_set(&self) -> JwkSet {
+        JwkSet {
+            keys: self.jwk().into_iter().collect(),
+        }
+    }
+
+    /// Returns `true` if this cipher is only able to decode.
+    pub fn is_verifier(&self) -> bool {
+        self.enc_key.is_none()
+    }
+
+    /// Sets the [ClaimsProjection] that is used when issuing a [BoardingPass].
+    pub fn with_claims_projection(
+        mut self,
+        claims_projection: ClaimsProjection,
+    ) -> Self {
+        self.claims_projection = claims_projection;
+        self
+    }
+
+    /// Returns the [Header] that is used for encoding.
+    fn header(&self) -> Header {
+        let mut header = Header::new(self.algorithm);
+        header.kid.clone_from(&self.kid);
+        header
+    }
+
+    /// Sets the [ValidationOptions] used for decoding and issuing.
+    pub fn with_validation_options(
+        mut self,
+        validation_options: ValidationOptions,
+    ) -> Self {
+        self.validation_options = validation_options;
+        self
+    }
+
+    /// Returns the [ValidationOptions] of this cipher.
+    pub fn validation_options(&self) -> &ValidationOptions {
+        &self.validation_options
+    }
+
+    /// Sets the [TokenLifetime] of issued tokens. Defaults to one week.
+    pub fn with_token_lifetime(
+        mut self,
+        token_lifetime: TokenLifetime,
+    ) -> Self {
+        self.token_lifetime = token_lifetime;
+        self
+    }
+
+    /// Returns the [TokenLifetime] of issued tokens.
+    pub fn token_lifetime(&self) -> &TokenLifetime {
+        &self.token_lifetime
+    }
+
+    /// Returns the [Validation] that is pinned to the cipher's [Algorithm].
+    fn validation(&self) -> Validation {
+        self.validation_options.validation(self.algorithm)
+    }
+
+    /// Ensures that the given [Algorithm] requires a key pair.
+    fn asymmetric_family(algorithm: Algorithm) -> anyhow::Result<KeyFamily> {
+        match KeyFamily::from(algorithm) {
+            KeyFamily::Hmac => Err(anyhow!(
+                "Algorithm {algorithm:?} requires a key pair instead of a \
+                 secret."
+            )),
+            family => Ok(family),
         }
     }
+
+    /// Reads the key at the given path.
+    fn read_key(path: impl AsRef<Path>) -> anyhow::Result<Vec<u8>> {
+        std::fs::read(path.as_ref()).map_err(|e| {
+            anyhow!("Could not read key {}: {e}", path.as_ref().display())
+        })
+    }
+}
+
+impl<AT> Issuing<JsonWebToken, AT> for JwtCipher
+where
+    AT: AuthType,
+{
+    fn issue(&self, passport: &Passport) -> BoardingPass<JsonWebToken, AT> {
+        BoardingPass::new(
+            JsonWebToken::new(
+                passport,
+                self.token_lifetime.lifetime(passport),
+                &self.validation_options,
+            )
+            .project(passport, &self.claims_projection),
+        )
+    }
 }
 
 impl<AT> Ciphering<JsonWebToken, AT, String> for JwtCipher
@@ -66,24 +571,27 @@ where
     fn encode(
         &self,
         boarding_pass: &BoardingPass<JsonWebToken, AT>,
-    ) -> Result<String, anyhow::Error> {
-        let web_token = jsonwebtoken::encode(
-            &Header::default(),
-            boarding_pass,
-            &self.enc_key,
-        )
-        .map_err(|e| anyhow!("{e}"))?;
+    ) -> Result<String, Error> {
+        let Some(enc_key) = &self.enc_key else {
+            return Err(Error::Configuration(
+                "JwtCipher is a verifier and is not able to encode."
+                    .to_string(),
+            ));
+        };
+        let web_token =
+            jsonwebtoken::encode(&self.header(), boarding_pass, enc_key)?;
         Ok(web_token)
     }
     fn decode(
         &self,
         encoded_value: &String,
-    ) -> Result<BoardingPass<JsonWebToken, AT>, anyhow::Error> {
+    ) -> Result<BoardingPass<JsonWebToken, AT>, Error> {
         let claims = jsonwebtoken::decode::<BoardingPass<JsonWebToken, AT>>(
             encoded_value,
             &self.dec_key,
-            &Validation::default(),
+            &self.validation(),
         )?;
+        self.validation_options.validate(&claims.claims.data)?;
         Ok(claims.claims)
     }
 }
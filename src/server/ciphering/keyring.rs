@@ -0,0 +1,253 @@
+//! Key rotation for [JwtCipher]s.
+//!
+//! A [JwtKeyring] holds several keys identified by their `kid` so the signing
+//! key can be replaced while tokens issued with the previous one stay valid.
+use super::{
+    Ciphering,
+    Issuing,
+    JwtCipher,
+    TokenLifetime,
+    ValidationOptions,
+};
+use crate::{
+    auth_type::AuthType,
+    boarding_pass::{
+        payloads::JsonWebToken,
+        BoardingPass,
+    },
+    error::Error,
+    passport::Passport,
+};
+use anyhow::anyhow;
+use chrono::{
+    DateTime,
+    TimeDelta,
+    Utc,
+};
+use jsonwebtoken::jwk::JwkSet;
+use std::collections::HashMap;
+
+/// A key of the [JwtKeyring].
+#[derive(Clone)]
+struct KeyringEntry {
+    cipher: JwtCipher,
+    /// The key is not accepted for verification after this point in time.
+    sunset: Option<DateTime<Utc>>,
+}
+
+impl KeyringEntry {
+    /// Returns `true` if the key is still accepted for verification.
+    fn is_valid(&self) -> bool {
+        self.sunset.map(|s| s > Utc::now()).unwrap_or(true)
+    }
+}
+
+/// A collection of [JwtCipher]s that enables key rotation.
+///
+/// New tokens are always signed with the active key whose id is stamped into
+/// the `kid` header. On decoding, the key is selected by the `kid` header of the
+/// token. Retired keys are accepted for verification until their sunset.
+///
+/// The [ValidationOptions] and [TokenLifetime] of the keyring are applied to
+/// all of its keys.
+#[derive(Clone)]
+pub struct JwtKeyring {
+    active: String,
+    keys: HashMap<String, KeyringEntry>,
+    validation_options: ValidationOptions,
+    token_lifetime: TokenLifetime,
+}
+
+impl JwtKeyring {
+    /// Creates a new keyring with the given cipher as active key. The
+    /// [ValidationOptions] and [TokenLifetime] of the cipher become the ones
+    /// of the keyring.
+    pub fn new(kid: &str, cipher: JwtCipher) -> anyhow::Result<Self> {
+        Self::ensure_signing(&cipher)?;
+        let validation_options = cipher.validation_options().clone();
+        let token_lifetime = cipher.token_lifetime().clone();
+        let mut keys = HashMap::new();
+        keys.insert(
+            kid.to_string(),
+            KeyringEntry {
+                cipher: cipher.with_kid(kid),
+                sunset: None,
+            },
+        );
+        Ok(Self {
+            active: kid.to_string(),
+            keys,
+            validation_options,
+            token_lifetime,
+        })
+    }
+
+    /// Sets the [ValidationOptions] for all keys of this keyring.
+    pub fn with_validation_options(
+        mut self,
+        validation_options: ValidationOptions,
+    ) -> Self {
+        for entry in self.keys.values_mut() {
+            entry.cipher = entry
+                .cipher
+                .clone()
+                .with_validation_options(validation_options.clone());
+        }
+        self.validation_options = validation_options;
+        self
+    }
+
+    /// Sets the [TokenLifetime] for all keys of this keyring.
+    pub fn with_token_lifetime(
+        mut self,
+        token_lifetime: TokenLifetime,
+    ) -> Self {
+        for entry in self.keys.values_mut() {
+            entry.cipher = entry
+                .cipher
+                .clone()
+                .with_token_lifetime(token_lifetime.clone());
+        }
+        self.token_lifetime = token_lifetime;
+        self
+    }
+
+    /// Makes the given cipher the active key. The previously active key is
+    /// retired and stays valid for verification for the given `grace_period`.
+    pub fn rotate(
+        &mut self,
+        kid: &str,
+        cipher: JwtCipher,
+        grace_period: TimeDelta,
+    ) -> anyhow::Result<()> {
+        Self::ensure_signing(&cipher)?;
+        if self.keys.contains_key(kid) {
+            return Err(anyhow!(
+                "Key with id {kid} is already in the keyring."
+            ));
+        }
+        if let Some(previous) = self.keys.get_mut(&self.active) {
+            previous.sunset = Some(Utc::now() + grace_period);
+        }
+        self.keys.insert(
+            kid.to_string(),
+            KeyringEntry {
+                cipher: cipher
+                    .with_kid(kid)
+                    .with_validation_options(self.validation_options.clone())
+                    .with_token_lifetime(self.token_lifetime.clone()),
+                sunset: None,
+            },
+        );
+        self.active = kid.to_string();
+        Ok(())
+    }
+
+    /// Adds a retired key that is only used for verification until `sunset`.
+    /// This is useful to keep tokens valid that have been signed before a
+    /// restart of your application.
+    pub fn add_retired(
+        &mut self,
+        kid: &str,
+        cipher: JwtCipher,
+        sunset: DateTime<Utc>,
+    ) -> anyhow::Result<()> {
+        if self.keys.contains_key(kid) {
+            return Err(anyhow!(
+                "Key with id {kid} is already in the keyring."
+            ));
+        }
+        self.keys.insert(
+            kid.to_string(),
+            KeyringEntry {
+                cipher: cipher
+                    .with_kid(kid)
+                    .with_validation_options(self.validation_options.clone()),
+                sunset: Some(sunset),
+            },
+        );
+        Ok(())
+    }
+
+    /// Removes all retired keys whose sunset has passed.
+    pub fn prune(&mut self) {
+        self.keys.retain(|_, entry| entry.is_valid());
+    }
+
+    /// Returns the id of the active key.
+    pub fn active_kid(&self) -> &str {
+        &self.active
+    }
+
+    /// Returns the cipher of the active key.
+    pub fn active(&self) -> &JwtCipher {
+        &self.keys[&self.active].cipher
+    }
+
+    /// Returns all ciphers that are currently accepted for verification.
+    pub fn ciphers(&self) -> impl Iterator<Item = &JwtCipher> {
+        self.keys
+            .values()
+            .filter(|entry| entry.is_valid())
+            .map(|entry| &entry.cipher)
+    }
+
+    /// Returns a [JwkSet] containing the public keys of all ciphers that are
+    /// currently accepted for verification.
+    pub fn jwk_set(&self) -> JwkSet {
+        JwkSet {
+            keys: self.ciphers().filter_map(|c| c.jwk()).collect(),
+        }
+    }
+
+    /// Ensures that the given cipher is able to sign tokens.
+    fn ensure_signing(cipher: &JwtCipher) -> anyhow::Result<()> {
+        if cipher.is_verifier() {
+            return Err(anyhow!(
+                "A verify only JwtCipher can not be used as active key."
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl<AT> Issuing<JsonWebToken, AT> for JwtKeyring
+where
+    AT: AuthType,
+{
+    fn issue(&self, passport: &Passport) -> BoardingPass<JsonWebToken, AT> {
+        self.active().issue(passport)
+    }
+}
+
+impl<AT> Ciphering<JsonWebToken, AT, String> for JwtKeyring
+where
+    AT: AuthType,
+{
+    fn encode(
+        &self,
+        boarding_pass: &BoardingPass<JsonWebToken, AT>,
+    ) -> Result<String, Error> {
+        self.active().encode(boarding_pass)
+    }
+    fn decode(
+        &self,
+        encoded_value: &String,
+    ) -> Result<BoardingPass<JsonWebToken, AT>, Error> {
+        let header = jsonwebtoken::decode_header(encoded_value)?;
+        let Some(kid) = header.kid else {
+            return Err(Error::InvalidToken(
+                "Token does not contain a key id.".to_string(),
+            ));
+        };
+        let Some(entry) = self.keys.get(&kid) else {
+            return Err(Error::InvalidToken(format!("Unknown key id {kid}.")));
+        };
+        if !entry.is_valid() {
+            return Err(Error::InvalidToken(format!(
+                "Key with id {kid} has been retired."
+            )));
+        }
+        entry.cipher.decode(encoded_value)
+    }
+}
@@ -0,0 +1,332 @@
+//! Configuration of `cosmodrome` using [rocket]s [Figment](rocket::figment::Figment).
+//!
+//! The configuration is read from the `cosmodrome` table, for example in your
+//! `Rocket.toml`:
+//!
+//! ```toml
+//! [default.cosmodrome]
+//! algorithm = "RS256"
+//! private_key = "keys/private.pem"
+//! public_key = "keys/public.pem"
+//! issuer = "https://auth.example.com"
+//! audiences = ["example"]
+//! token_lifetime = 604800
+//!
+//! [default.cosmodrome.token_lifetime_overrides]
+//! Admin = 900
+//!
+//! [default.cosmodrome.claims]
+//! services = true
+//!
+//! [default.cosmodrome.cookie]
+//! name = "session"
+//! ```
+//!
+//! or using environment variables, eg. `ROCKET_COSMODROME={secret="..."}`.
+use super::{
+    boarding_pass::payloads::ClaimsProjection,
+    ciphering::{
+        JwtCipher,
+        KeyFormat,
+        TokenLifetime,
+        ValidationOptions,
+    },
+    passport::PassportType,
+    storage::CookieStorageOptions,
+};
+use anyhow::anyhow;
+use chrono::TimeDelta;
+use jsonwebtoken::Algorithm;
+use log::warn;
+use rocket::{
+    fairing::AdHoc,
+    http::{
+        Cookie as RocketCookie,
+        SameSite as RocketSameSite,
+    },
+    serde::Deserialize,
+    Build,
+    Config,
+    Rocket,
+};
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+};
+
+/// The minimum length in bytes of [CosmodromeConfig::secret] outside of
+/// debug profile.
+const MIN_SECRET_LENGTH: usize = 32;
+
+/// The configuration of the `cosmodrome` table.
+#[derive(Deserialize, Clone)]
+#[serde(crate = "rocket::serde", default)]
+pub struct CosmodromeConfig {
+    /// The algorithm used for signing tokens. Defaults to `HS256`.
+    pub algorithm: Algorithm,
+    /// The secret used by `HS` algorithms. Needs to be at least 32 bytes long
+    /// outside of debug profile.
+    pub secret: Option<String>,
+    /// Path to the private key used by asymmetric algorithms.
+    pub private_key: Option<PathBuf>,
+    /// Path to the public key used by asymmetric algorithms. If no private key
+    /// is given, the cipher is verify only.
+    pub public_key: Option<PathBuf>,
+    /// The format of the key files. Defaults to `pem`.
+    pub key_format: KeyFormat,
+    /// The key id stamped into the header of each token.
+    pub kid: Option<String>,
+    /// See [ValidationOptions::issuer].
+    pub issuer: Option<String>,
+    /// See [ValidationOptions::audiences].
+    pub audiences: Vec<String>,
+    /// See [ValidationOptions::leeway]. Defaults to `60` seconds.
+    pub leeway: u64,
+    /// See [ValidationOptions::max_age], in seconds.
+    pub max_age: Option<i64>,
+    /// Lifetime of issued tokens in seconds. Defaults to one week.
+    pub token_lifetime: i64,
+    /// Lifetime of issued tokens in seconds for specific [PassportType]s.
+    pub token_lifetime_overrides: HashMap<PassportType, i64>,
+    /// The [ClaimsProjection] of issued tokens. Nothing is projected by
+    /// default.
+    pub claims: ClaimsProjection,
+    /// Settings of the cookie that stores the token.
+    pub cookie: CookieConfig,
+}
+
+impl Default for CosmodromeConfig {
+    fn default() -> Self {
+        Self {
+            algorithm: Algorithm::HS256,
+            secret: None,
+            private_key: None,
+            public_key: None,
+            key_format: KeyFormat::Pem,
+            kid: None,
+            issuer: None,
+            audiences: vec![],
+            leeway: ValidationOptions::default().leeway,
+            max_age: None,
+            token_lifetime: TimeDelta::weeks(1).num_seconds(),
+            token_lifetime_overrides: HashMap::new(),
+            claims: ClaimsProjection::default(),
+            cookie: CookieConfig::default(),
+        }
+    }
+}
+
+/// The `SameSite` attribute of the cookie.
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+pub enum SameSite {
+    /// `SameSite=Strict`
+    Strict,
+    /// `SameSite=Lax`
+    Lax,
+    /// `SameSite=None`
+    None,
+}
+
+impl From<SameSite> for RocketSameSite {
+    fn from(value: SameSite) -> Self {
+        match value {
+            SameSite::Strict => Self::Strict,
+            SameSite::Lax => Self::Lax,
+            SameSite::None => Self::None,
+        }
+    }
+}
+
+/// The settings of the `cosmodrome.cookie` table.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(crate = "rocket::serde", default)]
+pub struct CookieConfig {
+    /// Name of the cookie. Defaults to `cosmodrome`.
+    pub name: String,
+    /// Path of the cookie. Defaults to `/`.
+    pub path: String,
+    /// Domain of the cookie.
+    pub domain: Option<String>,
+    /// Whether the cookie is only sent over HTTPS. Defaults to `true`.
+    pub secure: bool,
+    /// Whether the cookie is hidden from scripts. Defaults to `true`.
+    pub http_only: bool,
+    /// Defaults to [SameSite::Strict].
+    pub same_site: SameSite,
+}
+
+impl Default for CookieConfig {
+    fn default() -> Self {
+        Self {
+            name: "cosmodrome".to_string(),
+            path: "/".to_string(),
+            domain: None,
+            secure: true,
+            http_only: true,
+            same_site: SameSite::Strict,
+        }
+    }
+}
+
+impl CosmodromeConfig {
+    /// Returns a fairing that reads the `cosmodrome` table at ignite and
+    /// manages the resulting [JwtCipher] and [CookieStorageOptions].
+    ///
+    /// If no key or secret is configured, a random cipher is created in debug
+    /// profile. In any other profile, ignite fails instead.
+    pub fn fairing() -> AdHoc {
+        AdHoc::try_on_ignite("cosmodrome configuration", |rocket| async {
+            let config = match rocket
+                .figment()
+                .extract_inner::<CosmodromeConfig>("cosmodrome")
+            {
+                Ok(c) => c,
+                Err(e) if e.missing() => CosmodromeConfig::default(),
+                Err(e) => {
+                    log::error!("Invalid cosmodrome configuration: {e}");
+                    return Err(rocket);
+                }
+            };
+            let cipher = match config.cipher(&rocket) {
+                Ok(c) => c,
+                Err(e) => {
+                    log::error!("{e}");
+                    return Err(rocket);
+                }
+            };
+            let cookie_options = config.cookie_storage_options();
+            Ok(rocket.manage(cipher).manage(cookie_options))
+        })
+    }
+
+    /// Creates the [JwtCipher] described by this configuration.
+    pub fn cipher(&self, rocket: &Rocket<Build>) -> anyhow::Result<JwtCipher> {
+        let cipher = match (&self.secret, &self.private_key, &self.public_key) {
+            (Some(secret), _, _) => {
+                check_secret(rocket, secret)?;
+                JwtCipher::from_secret(self.algorithm, secret.as_bytes())?
+            }
+            (None, Some(private_key), Some(public_key)) => {
+                JwtCipher::from_key_files(
+                    self.algorithm,
+                    self.key_format,
+                    private_key,
+                    public_key,
+                )?
+            }
+            (None, None, Some(public_key)) => JwtCipher::verifier_from_file(
+                self.algorithm,
+                self.key_format,
+                public_key,
+            )?,
+            (None, Some(_), None) => {
+                return Err(anyhow!(
+                    "cosmodrome.public_key is required when using a \
+                     private_key."
+                ));
+            }
+            (None, None, None) => {
+                if rocket.figment().profile() != Config::DEBUG_PROFILE {
+                    return Err(anyhow!(
+                        "No cosmodrome secret or key configured. A random \
+                         cipher is only allowed in debug profile."
+                    ));
+                }
+                warn!(
+                    "No cosmodrome secret or key configured, using a random \
+                     HS256 secret. All tokens become invalid on restart."
+                );
+                JwtCipher::random()
+            }
+        };
+        let cipher = match &self.kid {
+            Some(kid) => cipher.with_kid(kid),
+            None => cipher,
+        };
+        Ok(cipher
+            .with_claims_projection(self.claims.clone())
+            .with_validation_options(self.validation_options()?)
+            .with_token_lifetime(self.token_lifetime()?))
+    }
+
+    /// Creates the [ValidationOptions] described by this configuration.
+    pub fn validation_options(&self) -> anyhow::Result<ValidationOptions> {
+        Ok(ValidationOptions {
+            issuer: self.issuer.clone(),
+            audiences: self.audiences.clone(),
+            leeway: self.leeway,
+            max_age: self
+                .max_age
+                .map(|s| positive_seconds("max_age", s))
+                .transpose()?,
+            ..Default::default()
+        })
+    }
+
+    /// Creates the [TokenLifetime] described by this configuration.
+    pub fn token_lifetime(&self) -> anyhow::Result<TokenLifetime> {
+        self.token_lifetime_overrides.iter().try_fold(
+            TokenLifetime::new(positive_seconds(
+                "token_lifetime",
+                self.token_lifetime,
+            )?),
+            |lifetime, (passport_type, seconds)| {
+                Ok(lifetime.with_override(
+                    passport_type.clone(),
+                    positive_seconds("token_lifetime_overrides", *seconds)?,
+                ))
+            },
+        )
+    }
+
+    /// Creates the [CookieStorageOptions] described by this configuration.
+    /// The expiry of the cookie follows the `exp` claim of the stored token.
+    pub fn cookie_storage_options(&self) -> CookieStorageOptions<'static> {
+        let cookie = RocketCookie::build((self.cookie.name.clone(), ""))
+            .path(self.cookie.path.clone())
+            .secure(self.cookie.secure)
+            .http_only(self.cookie.http_only)
+            .same_site(self.cookie.same_site.into());
+        let cookie = match &self.cookie.domain {
+            Some(domain) => cookie.domain(domain.clone()),
+            None => cookie,
+        };
+        CookieStorageOptions::new(cookie.build())
+    }
+}
+
+/// Converts the configured value of `key` to a [TimeDelta]. Only positive
+/// values that fit into a [TimeDelta] are accepted.
+fn positive_seconds(key: &str, seconds: i64) -> anyhow::Result<TimeDelta> {
+    match TimeDelta::try_seconds(seconds) {
+        Some(delta) if seconds > 0 => Ok(delta),
+        _ => Err(anyhow!(
+            "cosmodrome.{key} must be a positive number of seconds, got \
+             {seconds}."
+        )),
+    }
+}
+
+/// Rejects an empty secret, and secrets shorter than [MIN_SECRET_LENGTH]
+/// outside of debug profile.
+fn check_secret(rocket: &Rocket<Build>, secret: &str) -> anyhow::Result<()> {
+    if secret.is_empty() {
+        return Err(anyhow!("cosmodrome.secret must not be empty."));
+    }
+    if secret.len() >= MIN_SECRET_LENGTH {
+        return Ok(());
+    }
+    if rocket.figment().profile() != Config::DEBUG_PROFILE {
+        return Err(anyhow!(
+            "cosmodrome.secret must be at least {MIN_SECRET_LENGTH} bytes \
+             long, got {} bytes.",
+            secret.len()
+        ));
+    }
+    warn!(
+        "cosmodrome.secret is shorter than {MIN_SECRET_LENGTH} bytes. This is \
+         only allowed in debug profile."
+    );
+    Ok(())
+}
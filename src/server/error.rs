@@ -0,0 +1,186 @@
+//! The [Error] type of `cosmodrome`.
+//!
+//! [Gate](super::gate::Gate)s, [PassportRegister](super::passport_register::PassportRegister)s,
+//! [BoardingPassStorage](super::storage::BoardingPassStorage)s,
+//! [Ciphering](super::ciphering::Ciphering) implementations, the storages of
+//! [refresh tokens](super::refresh::RefreshTokenStorage),
+//! [revocations](super::revocation::RevocationStorage),
+//! [sessions](super::session::SessionBackend) and
+//! [API keys](super::api_key::ApiKeyRegister) as well as the request guards
+//! return an [Error], so the reason of a failure can be matched
+//! instead of parsing messages. Use [Error::status] to get the corresponding
+//! HTTP [Status].
+//!
+//! Custom implementations wrap their own errors using [Error::storage] or
+//! [Error::Other].
+use super::passport::PassportStatusError;
+use jsonwebtoken::errors::ErrorKind;
+use rocket::http::Status;
+use std::fmt;
+
+/// Errors that occur during authentication and authorization.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// The request does not contain any credentials.
+    MissingCredentials,
+    /// The body of the request is missing or malformed.
+    BadRequest(String),
+    /// The presented credentials are wrong.
+    InvalidCredentials,
+    /// There is no [Passport](super::passport::Passport) with the given id.
+    PassportNotFound(String),
+    /// The [Passport](super::passport::Passport) has been rejected by the
+    /// [PassportStatusPolicy](super::passport::PassportStatusPolicy).
+    PassportStatus(PassportStatusError),
+    /// The token has expired.
+    TokenExpired,
+    /// The signature of the token is invalid.
+    InvalidSignature,
+    /// The token is malformed or its claims are not accepted.
+    InvalidToken(String),
+    /// The token has been revoked.
+    TokenRevoked,
+    /// The [Passport](super::passport::Passport) is authenticated, but does
+    /// not have the required permission.
+    Forbidden(String),
+    /// `cosmodrome` is not set up correctly, eg. a verify only cipher is used
+    /// to encode.
+    Configuration(String),
+    /// The underlying storage failed.
+    Storage(Box<dyn std::error::Error + Send + Sync>),
+    /// Any other error.
+    Other(anyhow::Error),
+}
+
+impl Error {
+    /// Wraps the given error of a storage backend.
+    pub fn storage<E>(error: E) -> Self
+    where
+        E: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        Self::Storage(error.into())
+    }
+
+    /// Returns a stable, machine readable code of this error, eg.
+    /// `token_expired`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::MissingCredentials => "missing_credentials",
+            Self::BadRequest(_) => "bad_request",
+            Self::InvalidCredentials => "invalid_credentials",
+            Self::PassportNotFound(_) => "passport_not_found",
+            Self::PassportStatus(PassportStatusError::Disabled) => {
+                "passport_disabled"
+            }
+            Self::PassportStatus(PassportStatusError::Unconfirmed) => {
+                "passport_unconfirmed"
+            }
+            Self::PassportStatus(PassportStatusError::Expired) => {
+                "passport_expired"
+            }
+            Self::TokenExpired => "token_expired",
+            Self::InvalidSignature => "invalid_signature",
+            Self::InvalidToken(_) => "invalid_token",
+            Self::TokenRevoked => "token_revoked",
+            Self::Forbidden(_) => "forbidden",
+            Self::Configuration(_) => "configuration",
+            Self::Storage(_) => "storage",
+            Self::Other(_) => "other",
+        }
+    }
+
+    /// Returns the HTTP [Status] that corresponds to this error.
+    ///
+    /// - `400 Bad Request` if the body of the request is malformed
+    /// - `401 Unauthorized` if the request could not be authenticated
+    /// - `403 Forbidden` if the passport is rejected or lacks a permission
+    /// - `500 Internal Server Error` in all other cases
+    pub fn status(&self) -> Status {
+        match self {
+            Self::BadRequest(_) => Status::BadRequest,
+            Self::MissingCredentials
+            | Self::InvalidCredentials
+            | Self::PassportNotFound(_)
+            | Self::TokenExpired
+            | Self::InvalidSignature
+            | Self::InvalidToken(_)
+            | Self::TokenRevoked => Status::Unauthorized,
+            Self::PassportStatus(_) | Self::Forbidden(_) => Status::Forbidden,
+            Self::Configuration(_) | Self::Storage(_) | Self::Other(_) => {
+                Status::InternalServerError
+            }
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingCredentials => write!(f, "No credentials available."),
+            Self::BadRequest(reason) => write!(f, "Bad request: {reason}"),
+            Self::InvalidCredentials => write!(f, "Invalid credentials."),
+            Self::PassportNotFound(id) => {
+                write!(f, "No passport found with id: {id}")
+            }
+            Self::PassportStatus(e) => write!(f, "{e}"),
+            Self::TokenExpired => write!(f, "Token has expired."),
+            Self::InvalidSignature => write!(f, "Invalid token signature."),
+            Self::InvalidToken(reason) => write!(f, "Invalid token: {reason}"),
+            Self::TokenRevoked => write!(f, "Token has been revoked."),
+            Self::Forbidden(reason) => write!(f, "{reason}"),
+            Self::Configuration(reason) => {
+                write!(f, "Invalid configuration: {reason}")
+            }
+            Self::Storage(e) => write!(f, "Storage error: {e}"),
+            Self::Other(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::PassportStatus(e) => Some(e),
+            Self::Storage(e) => Some(e.as_ref()),
+            Self::Other(e) => Some(e.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<PassportStatusError> for Error {
+    fn from(value: PassportStatusError) -> Self {
+        Self::PassportStatus(value)
+    }
+}
+
+impl From<jsonwebtoken::errors::Error> for Error {
+    fn from(value: jsonwebtoken::errors::Error) -> Self {
+        match value.kind() {
+            ErrorKind::ExpiredSignature => Self::TokenExpired,
+            ErrorKind::InvalidSignature => Self::InvalidSignature,
+            ErrorKind::InvalidKeyFormat
+            | ErrorKind::InvalidRsaKey(_)
+            | ErrorKind::InvalidEcdsaKey => {
+                Self::Configuration(value.to_string())
+            }
+            _ => Self::InvalidToken(value.to_string()),
+        }
+    }
+}
+
+/// Recovers an [Error] or [PassportStatusError] that has been wrapped into an
+/// [anyhow::Error], otherwise returns [Error::Other].
+impl From<anyhow::Error> for Error {
+    fn from(value: anyhow::Error) -> Self {
+        let value = match value.downcast::<Self>() {
+            Ok(e) => return e,
+            Err(e) => e,
+        };
+        match value.downcast::<PassportStatusError>() {
+            Ok(e) => Self::PassportStatus(e),
+            Err(e) => Self::Other(e),
+        }
+    }
+}
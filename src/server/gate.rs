@@ -5,16 +5,27 @@ use super::{
         AuthType,
         Bearer,
         Cookie,
+        Session,
     },
     boarding_pass::{
         payloads::JsonWebToken,
         BoardingPass,
     },
+    error::Error,
+    passport::{
+        Passport,
+        PassportStatusPolicy,
+    },
     passport_register::PassportRegister,
+    refresh::{
+        RefreshToken,
+        RefreshTokenStorage,
+        TokenPair,
+    },
+    revocation::RevocationStorage,
     storage::BoardingPassStorage,
 };
 use crate::Ticket;
-use anyhow::anyhow;
 
 /// A [Gate] is able to verify, grant and deny access to a [rocket].
 pub trait Gate<BPD, T, ID, ENC>
@@ -26,19 +37,113 @@ where
         ticket: Ticket,
         passport_register: &PR,
         boarding_pass_storage: &BPS,
-    ) -> anyhow::Result<String>
+    ) -> Result<String, Error>
     where
         BPS: BoardingPassStorage<BPD, T, ID, ENC>,
         PR: PassportRegister;
 
-    /// Executes a logout of the user.
-    fn logout<BPS>(
+    /// Verifies the given [Ticket] and checks the returned [Passport] against
+    /// the [PassportStatusPolicy](crate::passport::PassportStatusPolicy) of
+    /// the register.
+    ///
+    /// A rejected status is returned as [Error::PassportStatus].
+    fn check_in<PR>(
+        ticket: &Ticket,
+        passport_register: &PR,
+    ) -> Result<Passport, Error>
+    where
+        PR: PassportRegister,
+    {
+        let Some(passport) = passport_register.verify_credentials(ticket)?
+        else {
+            return Err(Error::PassportNotFound(ticket.id.clone()));
+        };
+        passport_register.status_policy().check(&passport)?;
+        Ok(passport)
+    }
+
+    /// Issues and stores a [BoardingPass] for the given, already verified,
+    /// [Passport].
+    fn board<BPS>(
+        passport: &Passport,
+        boarding_pass_storage: &BPS,
+    ) -> Result<ENC, Error>
+    where
+        BPS: BoardingPassStorage<BPD, T, ID, ENC>,
+    {
+        let boarding_pass: BoardingPass<BPD, T> =
+            boarding_pass_storage.issue_boarding_pass(passport)?;
+        boarding_pass_storage.store_boarding_pass(&boarding_pass)
+    }
+
+    /// Checks if the given [Ticket] is valid and generates a [BoardingPass]
+    /// together with a refresh token of a new family on success.
+    fn login_with_refresh<BPS, PR, RTS>(
+        ticket: Ticket,
+        passport_register: &PR,
+        boarding_pass_storage: &BPS,
+        refresh_token_storage: &RTS,
+    ) -> Result<TokenPair<ENC>, Error>
+    where
+        BPS: BoardingPassStorage<BPD, T, ID, ENC>,
+        PR: PassportRegister,
+        RTS: RefreshTokenStorage,
+    {
+        let passport = Self::check_in(&ticket, passport_register)?;
+        let access_token = Self::board(&passport, boarding_pass_storage)?;
+        let refresh_token =
+            refresh_token_storage.issue_refresh_token(&passport.id)?;
+        Ok(TokenPair {
+            access_token,
+            refresh_token: refresh_token.token,
+        })
+    }
+
+    /// Exchanges the given refresh token for a new [BoardingPass] and a
+    /// rotated refresh token.
+    ///
+    /// If the refresh token has already been used, its whole family is
+    /// revoked.
+    fn refresh<BPS, PR, RTS>(
+        refresh_token: &str,
+        passport_register: &PR,
+        boarding_pass_storage: &BPS,
+        refresh_token_storage: &RTS,
+    ) -> Result<TokenPair<ENC>, Error>
+    where
+        BPS: BoardingPassStorage<BPD, T, ID, ENC>,
+        PR: PassportRegister,
+        RTS: RefreshTokenStorage,
+    {
+        let refresh_token =
+            refresh_token_storage.rotate_refresh_token(refresh_token)?;
+        let passport = admit(
+            refresh_token_storage,
+            &refresh_token,
+            passport_register.passport(&refresh_token.passport_id)?,
+            passport_register.status_policy(),
+        )?;
+        let access_token = Self::board(&passport, boarding_pass_storage)?;
+        Ok(TokenPair {
+            access_token,
+            refresh_token: refresh_token.token,
+        })
+    }
+
+    /// Executes a logout of the user. The given [BoardingPass] is revoked,
+    /// so that it is rejected even if it is presented again before it
+    /// expires.
+    fn logout<BPS, RS>(
         identifier: ID,
+        boarding_pass: &BoardingPass<BPD, T>,
         boarding_pass_storage: &BPS,
-    ) -> anyhow::Result<()>
+        revocation_storage: &RS,
+    ) -> Result<(), Error>
     where
         BPS: BoardingPassStorage<BPD, T, ID, ENC>,
+        RS: RevocationStorage<BPD>,
     {
+        revocation_storage.revoke(&boarding_pass.data)?;
         boarding_pass_storage.remove_boarding_pass(identifier)
     }
 }
@@ -51,21 +156,13 @@ impl Gate<JsonWebToken, Cookie, (), String> for JwtCookieGate {
         ticket: Ticket,
         passport_register: &PR,
         boarding_pass_storage: &BPS,
-    ) -> anyhow::Result<String>
+    ) -> Result<String, Error>
     where
         BPS: BoardingPassStorage<JsonWebToken, Cookie, (), String>,
         PR: PassportRegister,
     {
-        let Some(passport) = passport_register.verify_credentials(&ticket)?
-        else {
-            return Err(anyhow!(
-                "No passport found for ticket with id: {}",
-                ticket.id
-            ));
-        };
-        let boarding_pass: BoardingPass<JsonWebToken, Cookie> =
-            BoardingPass::try_from(&passport)?;
-        boarding_pass_storage.store_boarding_pass(&boarding_pass)
+        let passport = Self::check_in(&ticket, passport_register)?;
+        Self::board(&passport, boarding_pass_storage)
     }
 }
 
@@ -77,20 +174,138 @@ impl Gate<JsonWebToken, Bearer, (), String> for JwtBearerGate {
         ticket: Ticket,
         passport_register: &PR,
         boarding_pass_storage: &BPS,
-    ) -> anyhow::Result<String>
+    ) -> Result<String, Error>
     where
         BPS: BoardingPassStorage<JsonWebToken, Bearer, (), String>,
         PR: PassportRegister,
     {
-        let Some(passport) = passport_register.verify_credentials(&ticket)?
-        else {
-            return Err(anyhow!(
-                "No passport found for ticket with id: {}",
-                ticket.id
-            ));
-        };
-        let boarding_pass: BoardingPass<JsonWebToken, Bearer> =
-            BoardingPass::try_from(&passport)?;
-        boarding_pass_storage.store_boarding_pass(&boarding_pass)
+        let passport = Self::check_in(&ticket, passport_register)?;
+        Self::board(&passport, boarding_pass_storage)
+    }
+}
+
+/// A gate where the [BoardingPass] is stored server side in a
+/// [SessionStore](crate::session::SessionStore) and only its session id is
+/// stored in a cookie.
+pub struct SessionGate;
+
+impl Gate<JsonWebToken, Session, String, String> for SessionGate {
+    fn login<BPS, PR>(
+        ticket: Ticket,
+        passport_register: &PR,
+        boarding_pass_storage: &BPS,
+    ) -> Result<String, Error>
+    where
+        BPS: BoardingPassStorage<JsonWebToken, Session, String, String>,
+        PR: PassportRegister,
+    {
+        let passport = Self::check_in(&ticket, passport_register)?;
+        Self::board(&passport, boarding_pass_storage)
+    }
+}
+
+/// Returns the [Passport] of a rotated refresh token. If it does not exist
+/// or is rejected by the [PassportStatusPolicy], the family of the token is
+/// revoked.
+pub(crate) fn admit<RTS>(
+    refresh_token_storage: &RTS,
+    refresh_token: &RefreshToken,
+    passport: Option<Passport>,
+    status_policy: PassportStatusPolicy,
+) -> Result<Passport, Error>
+where
+    RTS: RefreshTokenStorage,
+{
+    let error = match passport {
+        None => Error::PassportNotFound(refresh_token.passport_id.clone()),
+        Some(passport) => match status_policy.check(&passport) {
+            Ok(()) => return Ok(passport),
+            Err(e) => e.into(),
+        },
+    };
+    refresh_token_storage.revoke_family(&refresh_token.family)?;
+    Err(error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::{
+        ciphering::JwtCipher,
+        passport::{
+            PassportStatusError,
+            PassportType,
+        },
+        passport_register::MemoryPassportRegister,
+        refresh::MemoryRefreshTokenStorage,
+        storage::Storage,
+    };
+
+    type BearerStorage =
+        Storage<(), (), JsonWebToken, Bearer, JwtCipher, String>;
+
+    fn register() -> MemoryPassportRegister {
+        let register = MemoryPassportRegister::default();
+        let mut passport =
+            Passport::new("cosmo", "secret", &[], PassportType::User).unwrap();
+        passport.confirmed = true;
+        register.set_passport(passport).unwrap();
+        register
+    }
+
+    fn refresh(
+        token: &str,
+        register: &MemoryPassportRegister,
+        refresh_tokens: &MemoryRefreshTokenStorage,
+    ) -> Result<TokenPair, Error> {
+        let storage: BearerStorage = Storage::new((), (), JwtCipher::random());
+        JwtBearerGate::refresh(token, register, &storage, refresh_tokens)
+    }
+
+    #[test]
+    fn refresh_issues_access_token() {
+        let register = register();
+        let refresh_tokens = MemoryRefreshTokenStorage::default();
+        let issued = refresh_tokens.issue_refresh_token("cosmo").unwrap();
+        let token_pair =
+            refresh(&issued.token, &register, &refresh_tokens).unwrap();
+        assert!(!token_pair.access_token.is_empty());
+        assert_ne!(token_pair.refresh_token, issued.token);
+    }
+
+    #[test]
+    fn refresh_fails_for_missing_passport() {
+        let register = register();
+        let refresh_tokens = MemoryRefreshTokenStorage::default();
+        let issued = refresh_tokens.issue_refresh_token("naut").unwrap();
+        let result = refresh(&issued.token, &register, &refresh_tokens);
+        assert!(matches!(result, Err(Error::PassportNotFound(_))));
+        assert!(refresh_tokens
+            .refresh_token(&issued.token)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn refresh_fails_for_disabled_passport() {
+        let register = register();
+        let refresh_tokens = MemoryRefreshTokenStorage::default();
+        let issued = refresh_tokens.issue_refresh_token("cosmo").unwrap();
+        register
+            .update_passport("cosmo", &mut |p| {
+                p.disabled = true;
+                Ok(())
+            })
+            .unwrap();
+        let result = refresh(&issued.token, &register, &refresh_tokens);
+        assert!(matches!(
+            result,
+            Err(Error::PassportStatus(PassportStatusError::Disabled))
+        ));
+        // The family is revoked as well.
+        assert!(refresh_tokens
+            .refresh_token(&issued.token)
+            .unwrap()
+            .is_none());
     }
 }
@@ -21,8 +21,13 @@ use rocket::serde::{
     Deserialize,
     Serialize,
 };
+pub use status::{
+    PassportStatusError,
+    PassportStatusPolicy,
+};
 
 mod passport_type;
+mod status;
 
 /// Defines a passport of a user.
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -71,11 +76,38 @@ impl Passport {
         })
     }
 
+    /// Restores a passport from its stored values, eg. when loading it from a
+    /// database. The `password` needs to be hashed already.
+    pub fn from_parts(
+        id: &str,
+        password: &str,
+        services: Vec<String>,
+        account_type: PassportType,
+        disabled: bool,
+        confirmed: bool,
+        expires_at: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            id: id.to_string(),
+            password: password.to_string(),
+            services,
+            account_type,
+            disabled,
+            confirmed,
+            expires_at,
+        }
+    }
+
     /// Returns the services this passport is valid for.
     pub fn services(&self) -> &[String] {
         &self.services
     }
 
+    /// Returns the hash of the password.
+    pub fn password_hash(&self) -> &str {
+        &self.password
+    }
+
     /// Saves the ```new_password``` to the struct after verifying the ```old_password```.
     /// Does NOT automatically call the ```update``` function to update the database.
     pub fn change_password(
@@ -91,6 +123,18 @@ impl Passport {
         }
     }
 
+    /// Replaces the password without verifying the old one, eg. when it is
+    /// reset by an administrator.
+    pub fn set_password(&mut self, password: &str) -> anyhow::Result<()> {
+        self.password = Self::hash_password(password)?;
+        Ok(())
+    }
+
+    /// Replaces the services this passport is valid for.
+    pub fn set_services(&mut self, services: &[&str]) {
+        self.services = services.iter().map(|s| s.to_string()).collect();
+    }
+
     /// Checks if the given password is correct.
     pub fn verify_password(&self, password: &str) -> anyhow::Result<bool> {
         let hash = PasswordHash::parse(&self.password, Encoding::B64)
@@ -4,7 +4,7 @@ use rocket::serde::{
 };
 
 /// Defines the level of access of a [Passport](super::Passport).
-#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
 #[serde(crate = "rocket::serde")]
 pub enum PassportType {
     /// The person having this type is considered an Administrator.
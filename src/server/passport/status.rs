@@ -0,0 +1,84 @@
+use super::Passport;
+use chrono::Utc;
+use std::fmt;
+
+/// The reason why a [Passport] has been rejected by a
+/// [PassportStatusPolicy].
+///
+/// Gates and request guards return this error as
+/// [Error::PassportStatus](crate::Error::PassportStatus).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PassportStatusError {
+    /// The passport has been [disabled](Passport::disabled).
+    Disabled,
+    /// The passport has not been [confirmed](Passport::confirmed) yet.
+    Unconfirmed,
+    /// The passport has [expired](Passport::expires_at).
+    Expired,
+}
+
+impl fmt::Display for PassportStatusError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Disabled => write!(f, "Passport is disabled."),
+            Self::Unconfirmed => write!(f, "Passport has not been confirmed."),
+            Self::Expired => write!(f, "Passport has expired."),
+        }
+    }
+}
+
+impl std::error::Error for PassportStatusError {}
+
+/// Defines which status of a [Passport] is required to board.
+#[derive(Clone, Debug)]
+pub struct PassportStatusPolicy {
+    /// Rejects passports that are [disabled](Passport::disabled).
+    pub reject_disabled: bool,
+    /// Rejects passports that are not [confirmed](Passport::confirmed).
+    pub require_confirmed: bool,
+    /// Rejects passports whose [expires_at](Passport::expires_at) has passed.
+    pub reject_expired: bool,
+}
+
+impl Default for PassportStatusPolicy {
+    /// Default implementation:
+    ///
+    /// - Reject disabled: `true`
+    /// - Require confirmed: `true`
+    /// - Reject expired: `true`
+    fn default() -> Self {
+        Self {
+            reject_disabled: true,
+            require_confirmed: true,
+            reject_expired: true,
+        }
+    }
+}
+
+impl PassportStatusPolicy {
+    /// A policy that accepts every [Passport].
+    pub fn permissive() -> Self {
+        Self {
+            reject_disabled: false,
+            require_confirmed: false,
+            reject_expired: false,
+        }
+    }
+
+    /// Checks the given [Passport] against this policy.
+    pub fn check(
+        &self,
+        passport: &Passport,
+    ) -> Result<(), PassportStatusError> {
+        if self.reject_disabled && passport.disabled {
+            return Err(PassportStatusError::Disabled);
+        }
+        if self.require_confirmed && !passport.confirmed {
+            return Err(PassportStatusError::Unconfirmed);
+        }
+        if self.reject_expired && passport.expires_at <= Utc::now() {
+            return Err(PassportStatusError::Expired);
+        }
+        Ok(())
+    }
+}
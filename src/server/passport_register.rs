@@ -1,9 +1,31 @@
 //! A [PassportRegister] is a data structure that has access to all the registered users.
-use super::passport::Passport;
+//!
+//! All methods take `&self`, so a register that is managed by [rocket] can be
+//! modified from request handlers:
+//!
+//! ```rust,ignore
+//! #[post("/passports/<id>/disable")]
+//! fn disable(id: &str, register: &State<MemoryPassportRegister>) -> Result<(), Error> {
+//!     register.update_passport(id, &mut |p| {
+//!         p.disabled = true;
+//!         Ok(())
+//!     })?;
+//!     Ok(())
+//! }
+//! ```
+use super::{
+    error::Error,
+    passport::{
+        Passport,
+        PassportStatusPolicy,
+    },
+};
 use crate::Ticket;
-use anyhow::anyhow;
 use log::debug;
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    sync::RwLock,
+};
 
 /// The passport register contains a collection of passports that are
 /// known to your application.
@@ -11,23 +33,75 @@ use std::collections::HashMap;
 /// `ID` is the unique identifier type for a [Passport].
 pub trait PassportRegister {
     /// Returns the passport for the given `passport_id`.
-    fn passport(&self, passport_id: &str) -> anyhow::Result<Option<Passport>>;
+    fn passport(&self, passport_id: &str) -> Result<Option<Passport>, Error>;
+    /// Returns all passports of the register.
+    fn passports(&self) -> Result<Vec<Passport>, Error>;
     /// Stores the given passport in the register returning its ID for further usage.
-    fn set_passport(&mut self, passport: Passport) -> anyhow::Result<String>;
+    /// An existing passport with the same ID is replaced.
+    fn set_passport(&self, passport: Passport) -> Result<String, Error>;
+    /// Removes the passport with the given `passport_id`, returning it if it
+    /// existed.
+    fn remove_passport(
+        &self,
+        passport_id: &str,
+    ) -> Result<Option<Passport>, Error>;
+    /// Applies `update` to the passport with the given `passport_id` and
+    /// stores the result. Returns the updated passport, or `Ok(None)` if it
+    /// does not exist. Nothing is stored if `update` fails.
+    ///
+    /// The ID of the passport can not be changed this way, doing so results
+    /// in [Error::Configuration].
+    ///
+    /// The default implementation reads the passport and stores it again
+    /// using [set_passport](Self::set_passport), which is not atomic.
+    fn update_passport(
+        &self,
+        passport_id: &str,
+        update: &mut dyn FnMut(&mut Passport) -> Result<(), Error>,
+    ) -> Result<Option<Passport>, Error> {
+        let Some(mut passport) = self.passport(passport_id)? else {
+            return Ok(None);
+        };
+        update(&mut passport)?;
+        check_id(passport_id, &passport)?;
+        self.set_passport(passport.clone())?;
+        Ok(Some(passport))
+    }
     /// Verifies if the given [Ticket] is valid.
     /// Return scenarios should be the following:
     /// - If valid, a copy of the corresponding passport is returned.
     /// - If no corresponding [Passport] is found, the return value should be `Ok(None)`.
+    /// - If the secret is wrong, it should return [Error::InvalidCredentials].
     /// - In all other cases, it should return `Err(_)`.
     fn verify_credentials(
         &self,
         ticket: &Ticket,
-    ) -> anyhow::Result<Option<Passport>>;
+    ) -> Result<Option<Passport>, Error>;
+    /// Returns the [PassportStatusPolicy] that is applied by the gates at
+    /// login and by the [Traveler](crate::traveler::Traveler) guard on each
+    /// request.
+    fn status_policy(&self) -> PassportStatusPolicy {
+        PassportStatusPolicy::default()
+    }
 }
 
 /// A [MemoryPassportRegister] is a data structure where all [Passport]s are stored in memory.
+/// It can safely be shared between threads.
+#[derive(Default)]
 pub struct MemoryPassportRegister {
-    passports: HashMap<String, Passport>,
+    passports: RwLock<HashMap<String, Passport>>,
+    status_policy: PassportStatusPolicy,
+}
+
+impl MemoryPassportRegister {
+    /// Sets the [PassportStatusPolicy] of this register.
+    pub fn with_status_policy(
+        mut self,
+        status_policy: PassportStatusPolicy,
+    ) -> Self {
+        self.status_policy = status_policy;
+        self
+    }
 }
 
 impl From<Vec<Passport>> for MemoryPassportRegister {
@@ -36,23 +110,84 @@ impl From<Vec<Passport>> for MemoryPassportRegister {
         for val in value {
             passports.insert(val.id.clone(), val);
         }
-        Self { passports }
+        Self {
+            passports: RwLock::new(passports),
+            status_policy: PassportStatusPolicy::default(),
+        }
     }
 }
 
 impl PassportRegister for MemoryPassportRegister {
-    fn passport(&self, passport_id: &str) -> anyhow::Result<Option<Passport>> {
-        Ok(self.passports.get(passport_id).map(|p| p.to_owned()))
+    fn passport(&self, passport_id: &str) -> Result<Option<Passport>, Error> {
+        Ok(self
+            .passports
+            .read()
+            .map_err(|e| {
+                Error::storage(format!("Could not read passports: {e}"))
+            })?
+            .get(passport_id)
+            .map(|p| p.to_owned()))
+    }
+    fn passports(&self) -> Result<Vec<Passport>, Error> {
+        Ok(self
+            .passports
+            .read()
+            .map_err(|e| {
+                Error::storage(format!("Could not read passports: {e}"))
+            })?
+            .values()
+            .cloned()
+            .collect())
     }
-    fn set_passport(&mut self, passport: Passport) -> anyhow::Result<String> {
+    fn set_passport(&self, passport: Passport) -> Result<String, Error> {
         let id = passport.id.clone();
-        self.passports.insert(id.clone(), passport);
+        self.passports
+            .write()
+            .map_err(|e| {
+                Error::storage(format!("Could not write passports: {e}"))
+            })?
+            .insert(id.clone(), passport);
         Ok(id)
     }
+    fn remove_passport(
+        &self,
+        passport_id: &str,
+    ) -> Result<Option<Passport>, Error> {
+        Ok(self
+            .passports
+            .write()
+            .map_err(|e| {
+                Error::storage(format!("Could not write passports: {e}"))
+            })?
+            .remove(passport_id))
+    }
+    /// Applies `update` to a copy while holding the write lock, so concurrent
+    /// updates of the same passport are applied one after another.
+    ///
+    /// `update` must not use the register itself, doing so deadlocks. It also
+    /// blocks all other access to the register while it runs, so hash a new
+    /// password before calling this method.
+    fn update_passport(
+        &self,
+        passport_id: &str,
+        update: &mut dyn FnMut(&mut Passport) -> Result<(), Error>,
+    ) -> Result<Option<Passport>, Error> {
+        let mut passports = self.passports.write().map_err(|e| {
+            Error::storage(format!("Could not write passports: {e}"))
+        })?;
+        let Some(stored) = passports.get_mut(passport_id) else {
+            return Ok(None);
+        };
+        let mut passport = stored.clone();
+        update(&mut passport)?;
+        check_id(passport_id, &passport)?;
+        *stored = passport.clone();
+        Ok(Some(passport))
+    }
     fn verify_credentials(
         &self,
         ticket: &Ticket,
-    ) -> anyhow::Result<Option<Passport>> {
+    ) -> Result<Option<Passport>, Error> {
         let Some(passport) = self.passport(&ticket.id)? else {
             debug!("User with id {} not found.", ticket.id);
             return Ok(None);
@@ -60,7 +195,104 @@ impl PassportRegister for MemoryPassportRegister {
         if passport.verify_password(&ticket.secret)? {
             Ok(Some(passport))
         } else {
-            Err(anyhow!("Invalid credentials."))
+            Err(Error::InvalidCredentials)
+        }
+    }
+    fn status_policy(&self) -> PassportStatusPolicy {
+        self.status_policy.clone()
+    }
+}
+
+/// Rejects an updated [Passport] whose id differs from `passport_id`.
+pub(crate) fn check_id(
+    passport_id: &str,
+    passport: &Passport,
+) -> Result<(), Error> {
+    if passport.id != passport_id {
+        return Err(Error::Configuration(format!(
+            "The id of passport {passport_id} can not be changed."
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::passport::PassportType;
+    use std::{
+        sync::Arc,
+        thread,
+    };
+
+    #[test]
+    fn concurrent_updates_are_not_lost() {
+        let passport = Passport::from_parts(
+            "cosmo",
+            "",
+            vec![],
+            PassportType::User,
+            false,
+            true,
+            chrono::Utc::now(),
+        );
+        let register = Arc::new(MemoryPassportRegister::from(vec![passport]));
+        let threads = (0..8)
+            .map(|i| {
+                let register = Arc::clone(&register);
+                thread::spawn(move || {
+                    register
+                        .update_passport("cosmo", &mut |p| {
+                            let mut services = p.services().to_vec();
+                            services.push(i.to_string());
+                            thread::yield_now();
+                            p.set_services(
+                                &services
+                                    .iter()
+                                    .map(String::as_str)
+                                    .collect::<Vec<_>>(),
+                            );
+                            Ok(())
+                        })
+                        .unwrap()
+                })
+            })
+            .collect::<Vec<_>>();
+        for thread in threads {
+            thread.join().unwrap();
         }
+        let passport = register.passport("cosmo").unwrap().unwrap();
+        assert_eq!(passport.services().len(), 8);
+    }
+
+    #[test]
+    fn failed_update_is_not_stored() {
+        let passport = Passport::from_parts(
+            "cosmo",
+            "",
+            vec![],
+            PassportType::User,
+            false,
+            true,
+            chrono::Utc::now(),
+        );
+        let register = MemoryPassportRegister::from(vec![passport]);
+        let result = register.update_passport("cosmo", &mut |p| {
+            p.disabled = true;
+            Err(Error::BadRequest("rejected".to_string()))
+        });
+        assert!(result.is_err());
+        let result = register.update_passport("cosmo", &mut |p| {
+            p.id = "nova".to_string();
+            Ok(())
+        });
+        assert!(matches!(result, Err(Error::Configuration(_))));
+        let passport = register.passport("cosmo").unwrap().unwrap();
+        assert!(!passport.disabled);
+        assert!(register.passport("nova").unwrap().is_none());
+        assert!(register
+            .update_passport("nova", &mut |_| Ok(()))
+            .unwrap()
+            .is_none());
     }
 }
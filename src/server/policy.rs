@@ -0,0 +1,710 @@
+//! Attribute based authorization using [Policy]s.
+//!
+//! A [Policy] decides whether a [Passport] may perform an action on a
+//! [Resource]. Policies can be combined using [AllOf], [AnyOf] and [Not], and
+//! are registered by name in a [PolicyRegistry] that is managed by [rocket].
+//! Every [Decision] carries the rule that made it, so it can be written to an
+//! audit log.
+//!
+//! ```rust,ignore
+//! // a user may edit a post if they own it, or are a moderator of its service
+//! let registry = PolicyRegistry::default().with_policy(
+//!     "edit_post",
+//!     AnyOf::new(vec![
+//!         Box::new(IsOwner::new("owner")),
+//!         Box::new(AllOf::new(vec![
+//!             Box::new(HasRole::new(PassportType::Moderator)),
+//!             Box::new(HasResourceService::new("service")),
+//!         ])),
+//!     ]),
+//! );
+//!
+//! struct EditPost;
+//!
+//! impl Permission for EditPost {
+//!     const POLICY: &'static str = "edit_post";
+//!     const ACTION: &'static str = "edit";
+//!     const RESOURCE: &'static str = "post";
+//! }
+//!
+//! #[put("/<service>/<owner>/posts/<id>")]
+//! fn edit(service: &str, owner: &str, id: &str, _auth: Authorized<EditPost, Cookie>) { }
+//! ```
+use super::{
+    asynchronous::AsyncPassportRegister,
+    auth_type::AuthType,
+    authorization::RoleHierarchy,
+    boarding_pass::{
+        payloads::JsonWebToken,
+        BoardingPass,
+    },
+    error::Error,
+    passport::{
+        Passport,
+        PassportType,
+    },
+    passport_register::MemoryPassportRegister,
+    problem::fail,
+    traveler::Traveler,
+};
+use log::{
+    error,
+    info,
+};
+use rocket::{
+    http::Status,
+    request::{
+        FromRequest,
+        Outcome,
+        Request,
+    },
+    Ignite,
+    Rocket,
+    Sentinel,
+};
+use std::{
+    collections::HashMap,
+    fmt,
+    marker::PhantomData,
+};
+
+/// The resource an action is performed on.
+#[derive(Clone, Debug, Default)]
+pub struct Resource {
+    /// The kind of resource, eg. `post`.
+    pub kind: String,
+    /// Attributes of the resource, eg. its `owner`.
+    pub attributes: HashMap<String, String>,
+}
+
+impl Resource {
+    /// Creates a new resource of the given kind without attributes.
+    pub fn new(kind: &str) -> Self {
+        Self {
+            kind: kind.to_string(),
+            attributes: HashMap::new(),
+        }
+    }
+
+    /// Adds the given attribute.
+    pub fn with_attribute(mut self, name: &str, value: &str) -> Self {
+        self.attributes.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    /// Creates a resource of the given kind whose attributes are the named
+    /// path parameters of the route that matched the request.
+    pub fn from_path(kind: &str, request: &Request<'_>) -> Self {
+        let mut resource = Self::new(kind);
+        let Some(route) = request.route() else {
+            return resource;
+        };
+        let segments = request.uri().path().segments().collect::<Vec<_>>();
+        let route_segments =
+            route.uri.path().split('/').filter(|s| !s.is_empty());
+        for (i, segment) in route_segments.enumerate() {
+            let Some(name) =
+                segment.strip_prefix('<').and_then(|s| s.strip_suffix('>'))
+            else {
+                continue;
+            };
+            match name.strip_suffix("..") {
+                Some(name) => {
+                    resource.attributes.insert(
+                        name.to_string(),
+                        segments.get(i..).unwrap_or_default().join("/"),
+                    );
+                    break;
+                }
+                None => {
+                    if let Some(value) = segments.get(i) {
+                        resource
+                            .attributes
+                            .insert(name.to_string(), value.to_string());
+                    }
+                }
+            }
+        }
+        resource
+    }
+
+    /// Returns the value of the given attribute.
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes.get(name).map(|a| a.as_str())
+    }
+}
+
+/// The result of evaluating a [Policy].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Decision {
+    /// Whether the action is allowed.
+    pub allowed: bool,
+    /// The rule that made the decision.
+    pub rule: String,
+    /// Human readable explanation of the decision.
+    pub reason: String,
+}
+
+impl Decision {
+    /// Creates a decision allowing the action.
+    pub fn allow(rule: &str, reason: &str) -> Self {
+        Self {
+            allowed: true,
+            rule: rule.to_string(),
+            reason: reason.to_string(),
+        }
+    }
+
+    /// Creates a decision denying the action.
+    pub fn deny(rule: &str, reason: &str) -> Self {
+        Self {
+            allowed: false,
+            rule: rule.to_string(),
+            reason: reason.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Decision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let effect = match self.allowed {
+            true => "allowed",
+            false => "denied",
+        };
+        write!(f, "{effect} by {}: {}", self.rule, self.reason)
+    }
+}
+
+/// Decides whether a [Passport] may perform an action on a [Resource].
+pub trait Policy: Send + Sync {
+    /// Evaluates the policy.
+    fn evaluate(
+        &self,
+        passport: &Passport,
+        action: &str,
+        resource: &Resource,
+    ) -> Decision;
+}
+
+/// A named rule defined by a closure.
+pub struct Rule<F> {
+    name: String,
+    rule: F,
+}
+
+impl<F> Rule<F>
+where
+    F: Fn(&Passport, &str, &Resource) -> bool + Send + Sync,
+{
+    /// Creates a new rule with the given name.
+    pub fn new(name: &str, rule: F) -> Self {
+        Self {
+            name: name.to_string(),
+            rule,
+        }
+    }
+}
+
+impl<F> Policy for Rule<F>
+where
+    F: Fn(&Passport, &str, &Resource) -> bool + Send + Sync,
+{
+    fn evaluate(
+        &self,
+        passport: &Passport,
+        action: &str,
+        resource: &Resource,
+    ) -> Decision {
+        match (self.rule)(passport, action, resource) {
+            true => Decision::allow(&self.name, "rule matched"),
+            false => Decision::deny(&self.name, "rule did not match"),
+        }
+    }
+}
+
+/// Allows if the [id](Passport::id) of the passport equals the given
+/// attribute of the [Resource].
+pub struct IsOwner {
+    attribute: String,
+}
+
+impl IsOwner {
+    /// Creates a new instance comparing against the given attribute.
+    pub fn new(attribute: &str) -> Self {
+        Self {
+            attribute: attribute.to_string(),
+        }
+    }
+}
+
+impl Policy for IsOwner {
+    fn evaluate(
+        &self,
+        passport: &Passport,
+        _action: &str,
+        resource: &Resource,
+    ) -> Decision {
+        match resource.attribute(&self.attribute) {
+            Some(owner) if owner == passport.id => {
+                Decision::allow("is_owner", "passport owns the resource")
+            }
+            Some(_) => {
+                Decision::deny("is_owner", "passport does not own the resource")
+            }
+            None => Decision::deny(
+                "is_owner",
+                &format!("resource has no attribute {}", self.attribute),
+            ),
+        }
+    }
+}
+
+/// Allows if the [PassportType] of the passport satisfies the given role
+/// with respect to a [RoleHierarchy].
+pub struct HasRole {
+    role: PassportType,
+    hierarchy: RoleHierarchy,
+}
+
+impl HasRole {
+    /// Creates a new instance using the default [RoleHierarchy].
+    pub fn new(role: PassportType) -> Self {
+        Self {
+            role,
+            hierarchy: RoleHierarchy::default(),
+        }
+    }
+
+    /// Sets the [RoleHierarchy] that is used.
+    pub fn with_hierarchy(mut self, hierarchy: RoleHierarchy) -> Self {
+        self.hierarchy = hierarchy;
+        self
+    }
+}
+
+impl Policy for HasRole {
+    fn evaluate(
+        &self,
+        passport: &Passport,
+        _action: &str,
+        _resource: &Resource,
+    ) -> Decision {
+        let rule = format!("has_role({:?})", self.role);
+        match self.hierarchy.satisfies(&passport.account_type, &self.role) {
+            true => Decision::allow(&rule, "passport has the role"),
+            false => Decision::deny(&rule, "passport lacks the role"),
+        }
+    }
+}
+
+/// Allows if the [services](Passport::services) of the passport contain the
+/// service named by the given attribute of the [Resource].
+pub struct HasResourceService {
+    attribute: String,
+}
+
+impl HasResourceService {
+    /// Creates a new instance reading the service from the given attribute.
+    pub fn new(attribute: &str) -> Self {
+        Self {
+            attribute: attribute.to_string(),
+        }
+    }
+}
+
+impl Policy for HasResourceService {
+    fn evaluate(
+        &self,
+        passport: &Passport,
+        _action: &str,
+        resource: &Resource,
+    ) -> Decision {
+        let Some(service) = resource.attribute(&self.attribute) else {
+            return Decision::deny(
+                "has_resource_service",
+                &format!("resource has no attribute {}", self.attribute),
+            );
+        };
+        match passport.services().iter().any(|s| s == service) {
+            true => Decision::allow(
+                "has_resource_service",
+                &format!("passport has service {service}"),
+            ),
+            false => Decision::deny(
+                "has_resource_service",
+                &format!("passport lacks service {service}"),
+            ),
+        }
+    }
+}
+
+/// Allows if all of the given policies allow. The first denying policy makes
+/// the decision.
+pub struct AllOf {
+    policies: Vec<Box<dyn Policy>>,
+}
+
+impl AllOf {
+    /// Creates a new instance.
+    pub fn new(policies: Vec<Box<dyn Policy>>) -> Self {
+        Self { policies }
+    }
+}
+
+impl Policy for AllOf {
+    fn evaluate(
+        &self,
+        passport: &Passport,
+        action: &str,
+        resource: &Resource,
+    ) -> Decision {
+        let mut rules = vec![];
+        for policy in self.policies.iter() {
+            let decision = policy.evaluate(passport, action, resource);
+            if !decision.allowed {
+                return decision;
+            }
+            rules.push(decision.rule);
+        }
+        Decision::allow(
+            &format!("all({})", rules.join(", ")),
+            "all rules matched",
+        )
+    }
+}
+
+/// Allows if any of the given policies allows. The first allowing policy makes
+/// the decision.
+pub struct AnyOf {
+    policies: Vec<Box<dyn Policy>>,
+}
+
+impl AnyOf {
+    /// Creates a new instance.
+    pub fn new(policies: Vec<Box<dyn Policy>>) -> Self {
+        Self { policies }
+    }
+}
+
+impl Policy for AnyOf {
+    fn evaluate(
+        &self,
+        passport: &Passport,
+        action: &str,
+        resource: &Resource,
+    ) -> Decision {
+        let mut rules = vec![];
+        for policy in self.policies.iter() {
+            let decision = policy.evaluate(passport, action, resource);
+            if decision.allowed {
+                return decision;
+            }
+            rules.push(decision.rule);
+        }
+        Decision::deny(&format!("any({})", rules.join(", ")), "no rule matched")
+    }
+}
+
+/// Inverts the decision of the given policy.
+pub struct Not {
+    policy: Box<dyn Policy>,
+}
+
+impl Not {
+    /// Creates a new instance.
+    pub fn new(policy: Box<dyn Policy>) -> Self {
+        Self { policy }
+    }
+}
+
+impl Policy for Not {
+    fn evaluate(
+        &self,
+        passport: &Passport,
+        action: &str,
+        resource: &Resource,
+    ) -> Decision {
+        let decision = self.policy.evaluate(passport, action, resource);
+        Decision {
+            allowed: !decision.allowed,
+            rule: format!("not({})", decision.rule),
+            reason: decision.reason,
+        }
+    }
+}
+
+/// A collection of named [Policy]s. Manage an instance with [rocket] to use
+/// the [Authorized] guard.
+#[derive(Default)]
+pub struct PolicyRegistry {
+    policies: HashMap<String, Box<dyn Policy>>,
+}
+
+impl PolicyRegistry {
+    /// Registers the given policy under the given name.
+    pub fn with_policy<P>(mut self, name: &str, policy: P) -> Self
+    where
+        P: Policy + 'static,
+    {
+        self.policies.insert(name.to_string(), Box::new(policy));
+        self
+    }
+
+    /// Evaluates the policy with the given name. Returns an error if no such
+    /// policy is registered.
+    pub fn evaluate(
+        &self,
+        name: &str,
+        passport: &Passport,
+        action: &str,
+        resource: &Resource,
+    ) -> Result<Decision, Error> {
+        let Some(policy) = self.policies.get(name) else {
+            return Err(Error::Configuration(format!(
+                "Unknown policy {name}."
+            )));
+        };
+        let decision = policy.evaluate(passport, action, resource);
+        info!(
+            target: "cosmodrome::policy",
+            "{} {action} on {} {:?}, {name} {decision}",
+            passport.id,
+            resource.kind,
+            resource.attributes
+        );
+        Ok(decision)
+    }
+}
+
+/// Declares a permission at the type level, evaluated by the [Authorized]
+/// guard.
+pub trait Permission {
+    /// The name of the policy in the [PolicyRegistry].
+    const POLICY: &'static str;
+    /// The action that is performed.
+    const ACTION: &'static str;
+    /// The kind of [Resource] the action is performed on.
+    const RESOURCE: &'static str;
+}
+
+/// Request guard that evaluates the policy of the [Permission] `P` for the
+/// [Traveler] against the path parameters of the request, see
+/// [Resource::from_path].
+///
+/// Requires a [PolicyRegistry] and an instance of `PR` in [rocket]s global
+/// state.
+#[derive(Debug)]
+pub struct Authorized<P, AT, PR = MemoryPassportRegister>
+where
+    P: Permission,
+    AT: AuthType,
+{
+    /// The [Traveler] that has been authorized.
+    pub traveler: Traveler<AT, PR>,
+    /// The [Decision] that allowed the request.
+    pub decision: Decision,
+    phantom_permission: PhantomData<P>,
+}
+
+/// Aborts launch if no [PolicyRegistry] is managed or the [Traveler] guard
+/// would abort.
+impl<P, AT, PR> Sentinel for Authorized<P, AT, PR>
+where
+    P: Permission,
+    AT: AuthType,
+    Traveler<AT, PR>: Sentinel,
+{
+    fn abort(rocket: &Rocket<Ignite>) -> bool {
+        if rocket.state::<PolicyRegistry>().is_none() {
+            error!(
+                "A route requires authorization, but no cosmodrome \
+                 PolicyRegistry is managed by rocket."
+            );
+            return true;
+        }
+        Traveler::<AT, PR>::abort(rocket)
+    }
+}
+
+#[rocket::async_trait]
+impl<'r, P, AT, PR> FromRequest<'r> for Authorized<P, AT, PR>
+where
+    P: Permission + Send + Sync,
+    AT: AuthType + Send + Sync,
+    BoardingPass<JsonWebToken, AT>: FromRequest<'r, Error = Error>,
+    PR: AsyncPassportRegister + 'static,
+{
+    type Error = Error;
+
+    async fn from_request(
+        request: &'r Request<'_>,
+    ) -> Outcome<Self, Self::Error> {
+        let traveler = match request.guard::<Traveler<AT, PR>>().await {
+            Outcome::Success(t) => t,
+            Outcome::Error(e) => return Outcome::Error(e),
+            Outcome::Forward(s) => return Outcome::Forward(s),
+        };
+        let Some(registry) = request.rocket().state::<PolicyRegistry>() else {
+            error!(
+                "No cosmodrome PolicyRegistry managed by rocket. Please \
+                 create an instance and manage it with rocket."
+            );
+            return Outcome::Forward(Status::InternalServerError);
+        };
+        let resource = Resource::from_path(P::RESOURCE, request);
+        let decision = match registry.evaluate(
+            P::POLICY,
+            &traveler.passport,
+            P::ACTION,
+            &resource,
+        ) {
+            Ok(d) => d,
+            Err(e) => {
+                error!("{e}");
+                return Outcome::Forward(Status::InternalServerError);
+            }
+        };
+        if !decision.allowed {
+            return fail(request, Error::Forbidden(decision.to_string()));
+        }
+        Outcome::Success(Self {
+            traveler,
+            decision,
+            phantom_permission: PhantomData,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rocket::local::blocking::Client;
+
+    struct PathResource(Resource);
+
+    #[rocket::async_trait]
+    impl<'r> FromRequest<'r> for PathResource {
+        type Error = ();
+
+        async fn from_request(
+            request: &'r Request<'_>,
+        ) -> Outcome<Self, Self::Error> {
+            Outcome::Success(Self(Resource::from_path("post", request)))
+        }
+    }
+
+    #[rocket::get("/<service>/posts/<id>/<rest..>")]
+    fn post(
+        service: &str,
+        id: &str,
+        rest: std::path::PathBuf,
+        resource: PathResource,
+    ) -> String {
+        let _ = (service, id, rest);
+        let mut attributes = resource
+            .0
+            .attributes
+            .iter()
+            .map(|(name, value)| format!("{name}={value}"))
+            .collect::<Vec<_>>();
+        attributes.sort();
+        format!("{} {}", resource.0.kind, attributes.join(" "))
+    }
+
+    fn passport(id: &str, account_type: PassportType) -> Passport {
+        Passport::from_parts(
+            id,
+            "",
+            vec!["blog".to_string()],
+            account_type,
+            false,
+            true,
+            chrono::Utc::now(),
+        )
+    }
+
+    fn post_of(owner: &str) -> Resource {
+        Resource::new("post")
+            .with_attribute("owner", owner)
+            .with_attribute("service", "blog")
+    }
+
+    #[test]
+    fn from_path_reads_route_parameters() {
+        let rocket = rocket::build().mount("/api", rocket::routes![post]);
+        let client = Client::tracked(rocket).unwrap();
+        let body = client
+            .get("/api/blog/posts/42/comments/7")
+            .dispatch()
+            .into_string()
+            .unwrap();
+        assert_eq!(body, "post id=42 rest=comments/7 service=blog");
+    }
+
+    #[test]
+    fn combinators_decide_by_first_relevant_policy() {
+        let policy = AnyOf::new(vec![
+            Box::new(IsOwner::new("owner")),
+            Box::new(AllOf::new(vec![
+                Box::new(HasRole::new(PassportType::Moderator)),
+                Box::new(HasResourceService::new("service")),
+            ])),
+        ]);
+        let user = passport("alice", PassportType::User);
+        let admin = passport("root", PassportType::Admin);
+
+        let decision = policy.evaluate(&user, "edit", &post_of("alice"));
+        assert!(decision.allowed);
+        assert_eq!(decision.rule, "is_owner");
+
+        let decision = policy.evaluate(&user, "edit", &post_of("bob"));
+        assert!(!decision.allowed);
+        assert_eq!(decision.rule, "any(is_owner, has_role(Moderator))");
+
+        let decision = policy.evaluate(&admin, "edit", &post_of("bob"));
+        assert!(decision.allowed);
+        assert_eq!(
+            decision.rule,
+            "all(has_role(Moderator), has_resource_service)"
+        );
+    }
+
+    #[test]
+    fn not_inverts_decision() {
+        let policy = Not::new(Box::new(IsOwner::new("owner")));
+        let user = passport("alice", PassportType::User);
+        let decision = policy.evaluate(&user, "delete", &post_of("alice"));
+        assert!(!decision.allowed);
+        assert_eq!(decision.rule, "not(is_owner)");
+        let decision = policy.evaluate(&user, "delete", &Resource::new("post"));
+        assert!(decision.allowed);
+    }
+
+    #[test]
+    fn registry_rejects_unknown_policy() {
+        let registry = PolicyRegistry::default().with_policy(
+            "reader",
+            Rule::new("reader", |_: &Passport, action: &str, _: &Resource| {
+                action == "read"
+            }),
+        );
+        let user = passport("alice", PassportType::User);
+        let resource = Resource::new("post");
+        assert!(
+            registry
+                .evaluate("reader", &user, "read", &resource)
+                .unwrap()
+                .allowed
+        );
+        assert!(
+            !registry
+                .evaluate("reader", &user, "edit", &resource)
+                .unwrap()
+                .allowed
+        );
+        assert!(matches!(
+            registry.evaluate("writer", &user, "read", &resource),
+            Err(Error::Configuration(_))
+        ));
+    }
+}
@@ -0,0 +1,171 @@
+//! The [Preflight] fairing validates the setup of `cosmodrome` before your
+//! [rocket] lifts off.
+//!
+//! ```rust,ignore
+//! rocket::build()
+//!     .manage(JwtCipher::random())
+//!     .manage(MemoryPassportRegister::from(passports))
+//!     .attach(Preflight::<MemoryPassportRegister>::new())
+//! ```
+use super::{
+    asynchronous::AsyncPassportRegister,
+    ciphering::{
+        is_managed,
+        JwtCipher,
+        JwtKeyring,
+    },
+    jwks::JwksVerifier,
+    passport_register::MemoryPassportRegister,
+    revocation::RevocationList,
+    storage::CookieStorageOptions,
+};
+use log::{
+    error,
+    info,
+    warn,
+};
+use rocket::{
+    fairing::{
+        self,
+        Fairing,
+        Info,
+        Kind,
+    },
+    Build,
+    Config,
+    Rocket,
+};
+use std::marker::PhantomData;
+
+/// Fairing that checks at ignite that a cipher and the
+/// [PassportRegister](super::passport_register::PassportRegister) `PR`
+/// are managed by [rocket], and that the [CookieStorageOptions] are sane.
+///
+/// All problems are logged. If at least one of them is an error, ignition
+/// fails so that a misconfiguration is detected at boot and not on the first
+/// request.
+///
+/// Ignite fairings run in the order they are attached, so attach this one
+/// after the [CosmodromeConfig](super::config::CosmodromeConfig) fairing.
+pub struct Preflight<PR = MemoryPassportRegister> {
+    phantom_register: PhantomData<fn() -> PR>,
+}
+
+impl<PR> Preflight<PR>
+where
+    PR: AsyncPassportRegister + 'static,
+{
+    /// Creates a new instance.
+    pub fn new() -> Self {
+        Self {
+            phantom_register: PhantomData,
+        }
+    }
+
+    /// Runs all checks, returning the errors that have been found.
+    fn check(rocket: &Rocket<Build>) -> Vec<String> {
+        let mut errors = vec![];
+        let managed_ciphers = [
+            rocket.state::<JwtCipher>().is_some(),
+            rocket.state::<JwtKeyring>().is_some(),
+            rocket.state::<JwksVerifier>().is_some(),
+        ];
+        if !is_managed(rocket) {
+            errors.push(
+                "No cosmodrome JwtCipher, JwtKeyring or JwksVerifier is \
+                 managed by rocket."
+                    .to_string(),
+            );
+        } else if managed_ciphers.iter().filter(|m| **m).count() > 1 {
+            warn!(
+                "More than one cosmodrome cipher is managed by rocket. Tokens \
+                 are decoded by the JwtCipher first, followed by the \
+                 JwtKeyring and the JwksVerifier."
+            );
+        }
+        let can_issue = rocket
+            .state::<JwtCipher>()
+            .map(|c| !c.is_verifier())
+            .unwrap_or(false)
+            || rocket.state::<JwtKeyring>().is_some();
+        if is_managed(rocket) && !can_issue {
+            warn!(
+                "The managed cosmodrome cipher is verify only, no boarding \
+                 passes can be issued by this rocket."
+            );
+        }
+        if rocket.state::<PR>().is_none() {
+            errors.push(format!(
+                "No cosmodrome PassportRegister of type {} is managed by \
+                 rocket.",
+                std::any::type_name::<PR>()
+            ));
+        }
+        if rocket.state::<RevocationList>().is_none() {
+            info!(
+                "No cosmodrome RevocationList managed by rocket, boarding \
+                 passes stay valid until they expire."
+            );
+        }
+        match rocket.state::<CookieStorageOptions>() {
+            None => info!(
+                "No cosmodrome CookieStorageOptions managed by rocket, using \
+                 defaults."
+            ),
+            Some(options) => {
+                let cookie = &options.cookie_template;
+                if cookie.name().is_empty() {
+                    errors.push(
+                        "The name of the cosmodrome cookie must not be empty."
+                            .to_string(),
+                    );
+                }
+                if cookie.secure() != Some(true) {
+                    if rocket.figment().profile() != Config::DEBUG_PROFILE {
+                        errors.push(
+                            "The cosmodrome cookie must be secure outside of \
+                             debug profile."
+                                .to_string(),
+                        );
+                    } else {
+                        warn!("The cosmodrome cookie is not secure.");
+                    }
+                }
+            }
+        }
+        errors
+    }
+}
+
+impl<PR> Default for Preflight<PR>
+where
+    PR: AsyncPassportRegister + 'static,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[rocket::async_trait]
+impl<PR> Fairing for Preflight<PR>
+where
+    PR: AsyncPassportRegister + 'static,
+{
+    fn info(&self) -> Info {
+        Info {
+            name: "cosmodrome preflight",
+            kind: Kind::Ignite,
+        }
+    }
+
+    async fn on_ignite(&self, rocket: Rocket<Build>) -> fairing::Result {
+        let errors = Self::check(&rocket);
+        if errors.is_empty() {
+            return Ok(rocket);
+        }
+        for e in errors {
+            error!("{e}");
+        }
+        Err(rocket)
+    }
+}
@@ -0,0 +1,175 @@
+//! Responses for failed authentication and authorization.
+//!
+//! An [Error] is rendered as JSON problem document
+//! ([RFC 7807](https://www.rfc-editor.org/rfc/rfc7807)). Clients that prefer
+//! HTML are redirected to the login URL of the managed [ProblemOptions]
+//! instead, if the request could not be authenticated.
+//!
+//! The request guards remember why they failed, so the [catchers] render the
+//! reason of a rejected request instead of rocket's default page.
+//!
+//! ```rust,ignore
+//! rocket::build()
+//!     .manage(ProblemOptions::default().with_login_url("/login"))
+//!     .register("/", cosmodrome::problem::catchers())
+//! ```
+use super::error::Error;
+use rocket::{
+    catcher::BoxFuture,
+    http::{
+        ContentType,
+        Status,
+    },
+    request::Outcome,
+    response::{
+        self,
+        Redirect,
+        Responder,
+    },
+    serde::{
+        json::serde_json,
+        Serialize,
+    },
+    Catcher,
+    Request,
+    Response,
+};
+use std::{
+    io::Cursor,
+    sync::Mutex,
+};
+
+/// Options for rendering a [Problem]. Manage an instance with [rocket] to
+/// change the defaults.
+#[derive(Clone, Debug, Default)]
+pub struct ProblemOptions {
+    /// The URL that clients preferring HTML are redirected to if the request
+    /// could not be authenticated. If not set, a [Problem] is rendered.
+    pub login_url: Option<String>,
+}
+
+impl ProblemOptions {
+    /// Sets the URL of the login page.
+    pub fn with_login_url(mut self, login_url: &str) -> Self {
+        self.login_url = Some(login_url.to_string());
+        self
+    }
+}
+
+/// A problem document as defined in
+/// [RFC 7807](https://www.rfc-editor.org/rfc/rfc7807).
+#[derive(Clone, Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct Problem {
+    /// A URI reference that identifies the problem type.
+    #[serde(rename = "type")]
+    pub problem_type: String,
+    /// A short summary of the problem type.
+    pub title: String,
+    /// The HTTP status code.
+    pub status: u16,
+    /// An explanation specific to this occurrence of the problem.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    /// The [code](Error::code) of the [Error] that caused the problem.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+}
+
+impl Problem {
+    /// Creates a problem for the given [Status] without further details.
+    pub fn new(status: Status) -> Self {
+        Self {
+            problem_type: "about:blank".to_string(),
+            title: status.reason_lossy().to_string(),
+            status: status.code,
+            detail: None,
+            code: None,
+        }
+    }
+}
+
+impl From<&Error> for Problem {
+    /// The detail of errors resulting in `500 Internal Server Error` is not
+    /// disclosed.
+    fn from(value: &Error) -> Self {
+        let status = value.status();
+        let mut problem = Self::new(status);
+        problem.code = Some(value.code().to_string());
+        if status != Status::InternalServerError {
+            problem.detail = Some(value.to_string());
+        }
+        problem
+    }
+}
+
+impl<'r> Responder<'r, 'static> for Problem {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        if self.status == Status::Unauthorized.code && prefers_html(request) {
+            let login_url = request
+                .rocket()
+                .state::<ProblemOptions>()
+                .and_then(|o| o.login_url.clone());
+            if let Some(login_url) = login_url {
+                return Redirect::to(login_url).respond_to(request);
+            }
+        }
+        let body = serde_json::to_string(&self)
+            .map_err(|_| Status::InternalServerError)?;
+        Response::build()
+            .status(Status::new(self.status))
+            .header(ContentType::new("application", "problem+json"))
+            .sized_body(body.len(), Cursor::new(body))
+            .ok()
+    }
+}
+
+impl<'r> Responder<'r, 'static> for Error {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        Problem::from(&self).respond_to(request)
+    }
+}
+
+/// Returns catchers for `401 Unauthorized` and `403 Forbidden` that render
+/// the reason of the failed request guard as [Problem].
+pub fn catchers() -> Vec<Catcher> {
+    vec![
+        Catcher::new(Status::Unauthorized.code, catch),
+        Catcher::new(Status::Forbidden.code, catch),
+    ]
+}
+
+/// Renders the [Problem] remembered by the failed request guard.
+fn catch<'r>(status: Status, request: &'r Request<'_>) -> BoxFuture<'r> {
+    let failure = request.local_cache(Failure::default).0.lock();
+    let problem = match failure.as_deref() {
+        Ok(Some(problem)) if problem.status == status.code => problem.clone(),
+        _ => Problem::new(status),
+    };
+    Box::pin(async move { problem.respond_to(request) })
+}
+
+/// The [Problem] of the last failed request guard of a request.
+#[derive(Default)]
+struct Failure(Mutex<Option<Problem>>);
+
+/// Fails the request guard with the given [Error], remembering it for the
+/// [catchers]. A later failure replaces the remembered one, so the catchers
+/// render the error of the guard that finally rejected the request.
+pub(crate) fn fail<T>(
+    request: &Request<'_>,
+    error: Error,
+) -> Outcome<T, Error> {
+    if let Ok(mut problem) = request.local_cache(Failure::default).0.lock() {
+        *problem = Some(Problem::from(&error));
+    }
+    Outcome::Error((error.status(), error))
+}
+
+/// Returns `true` if the client prefers HTML over JSON.
+fn prefers_html(request: &Request<'_>) -> bool {
+    request
+        .accept()
+        .map(|a| a.preferred().media_type().is_html())
+        .unwrap_or(false)
+}
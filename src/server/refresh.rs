@@ -0,0 +1,347 @@
+//! Refresh tokens that can be exchanged for a new [BoardingPass](super::boarding_pass::BoardingPass).
+//!
+//! Refresh tokens are opaque random strings. Every time a refresh token is
+//! used, it is rotated, meaning that a new refresh token of the same family is
+//! issued and the used one is marked. If a used refresh token is presented
+//! again, it has most likely been stolen, so the whole family is revoked. See
+//! [Gate::login_with_refresh](super::gate::Gate::login_with_refresh) and
+//! [Gate::refresh](super::gate::Gate::refresh).
+use super::error::Error;
+use chrono::{
+    DateTime,
+    TimeDelta,
+    Utc,
+};
+use rocket::serde::{
+    Deserialize,
+    Serialize,
+};
+use std::{
+    collections::HashMap,
+    sync::RwLock,
+};
+
+/// An access token together with the refresh token that can be used to
+/// obtain a new one.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct TokenPair<ENC = String> {
+    /// The encoded [BoardingPass](super::boarding_pass::BoardingPass).
+    pub access_token: ENC,
+    /// The refresh token.
+    pub refresh_token: String,
+}
+
+/// The state of a refresh token.
+#[derive(Clone, Debug)]
+pub struct RefreshToken {
+    /// The value that is handed out to the client.
+    pub token: String,
+    /// Identifier shared by all tokens that originate from the same login.
+    pub family: String,
+    /// The [id](super::passport::Passport::id) of the passport the token
+    /// has been issued for.
+    pub passport_id: String,
+    /// Timestamp after which the token is not accepted anymore.
+    pub expires_at: DateTime<Utc>,
+    /// Whether the token has already been exchanged.
+    pub used: bool,
+}
+
+impl RefreshToken {
+    /// Creates a new, unused token for the given family.
+    pub fn new(family: &str, passport_id: &str, lifetime: TimeDelta) -> Self {
+        Self {
+            token: generate_token(),
+            family: family.to_string(),
+            passport_id: passport_id.to_string(),
+            expires_at: Utc::now() + lifetime,
+            used: false,
+        }
+    }
+
+    /// Returns `true` if the token has not expired yet.
+    pub fn is_valid(&self) -> bool {
+        self.expires_at > Utc::now()
+    }
+}
+
+/// Storage for the state of refresh tokens.
+///
+/// Used tokens need to be kept until they expire, otherwise their reuse can
+/// not be detected.
+pub trait RefreshTokenStorage {
+    /// The lifetime of newly issued refresh tokens.
+    fn lifetime(&self) -> TimeDelta;
+    /// Returns the state of the given token.
+    fn refresh_token(&self, token: &str)
+        -> Result<Option<RefreshToken>, Error>;
+    /// Stores the given token.
+    fn store_refresh_token(
+        &self,
+        refresh_token: RefreshToken,
+    ) -> Result<(), Error>;
+    /// Marks the given token as used and returns its state from before.
+    ///
+    /// This needs to be atomic, so that a token can not be exchanged twice by
+    /// concurrent requests.
+    fn use_refresh_token(
+        &self,
+        token: &str,
+    ) -> Result<Option<RefreshToken>, Error>;
+    /// Removes all tokens of the given family.
+    fn revoke_family(&self, family: &str) -> Result<(), Error>;
+
+    /// Issues a refresh token of a new family for the given passport.
+    fn issue_refresh_token(
+        &self,
+        passport_id: &str,
+    ) -> Result<RefreshToken, Error> {
+        let refresh_token =
+            RefreshToken::new(&generate_token(), passport_id, self.lifetime());
+        self.store_refresh_token(refresh_token.clone())?;
+        Ok(refresh_token)
+    }
+
+    /// Exchanges the given token for a new one of the same family.
+    ///
+    /// If the token has already been used, the whole family is revoked.
+    /// Unknown, reused and expired tokens are rejected with
+    /// [Error::InvalidToken].
+    fn rotate_refresh_token(&self, token: &str) -> Result<RefreshToken, Error> {
+        let Some(previous) = self.use_refresh_token(token)? else {
+            return Err(invalid("Unknown refresh token."));
+        };
+        if previous.used {
+            self.revoke_family(&previous.family)?;
+            return Err(invalid(
+                "Refresh token has already been used, revoked its family.",
+            ));
+        }
+        if !previous.is_valid() {
+            return Err(invalid("Refresh token has expired."));
+        }
+        let refresh_token = RefreshToken::new(
+            &previous.family,
+            &previous.passport_id,
+            self.lifetime(),
+        );
+        self.store_refresh_token(refresh_token.clone())?;
+        Ok(refresh_token)
+    }
+
+    /// Revokes the family of the given token, eg. on logout.
+    fn revoke_refresh_token(&self, token: &str) -> Result<(), Error> {
+        match self.refresh_token(token)? {
+            Some(refresh_token) => self.revoke_family(&refresh_token.family),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A [RefreshTokenStorage] where all tokens are stored in memory.
+pub struct MemoryRefreshTokenStorage {
+    lifetime: TimeDelta,
+    tokens: RwLock<HashMap<String, RefreshToken>>,
+}
+
+impl Default for MemoryRefreshTokenStorage {
+    /// Creates a storage with a lifetime of `30 days`.
+    fn default() -> Self {
+        Self::new(TimeDelta::days(30))
+    }
+}
+
+impl MemoryRefreshTokenStorage {
+    /// Creates a new storage issuing tokens with the given lifetime.
+    pub fn new(lifetime: TimeDelta) -> Self {
+        Self {
+            lifetime,
+            tokens: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Removes all expired tokens.
+    pub fn prune(&self) -> Result<(), Error> {
+        self.tokens
+            .write()
+            .map_err(|e| {
+                Error::storage(format!("Could not write refresh tokens: {e}"))
+            })?
+            .retain(|_, refresh_token| refresh_token.is_valid());
+        Ok(())
+    }
+}
+
+impl RefreshTokenStorage for MemoryRefreshTokenStorage {
+    fn lifetime(&self) -> TimeDelta {
+        self.lifetime
+    }
+    fn refresh_token(
+        &self,
+        token: &str,
+    ) -> Result<Option<RefreshToken>, Error> {
+        Ok(self
+            .tokens
+            .read()
+            .map_err(|e| {
+                Error::storage(format!("Could not read refresh tokens: {e}"))
+            })?
+            .get(token)
+            .cloned())
+    }
+    fn store_refresh_token(
+        &self,
+        refresh_token: RefreshToken,
+    ) -> Result<(), Error> {
+        self.tokens
+            .write()
+            .map_err(|e| {
+                Error::storage(format!("Could not write refresh tokens: {e}"))
+            })?
+            .insert(refresh_token.token.clone(), refresh_token);
+        Ok(())
+    }
+    fn use_refresh_token(
+        &self,
+        token: &str,
+    ) -> Result<Option<RefreshToken>, Error> {
+        let mut tokens = self.tokens.write().map_err(|e| {
+            Error::storage(format!("Could not write refresh tokens: {e}"))
+        })?;
+        Ok(tokens.get_mut(token).map(|refresh_token| {
+            let previous = refresh_token.clone();
+            refresh_token.used = true;
+            previous
+        }))
+    }
+    fn revoke_family(&self, family: &str) -> Result<(), Error> {
+        self.tokens
+            .write()
+            .map_err(|e| {
+                Error::storage(format!("Could not write refresh tokens: {e}"))
+            })?
+            .retain(|_, refresh_token| refresh_token.family != family);
+        Ok(())
+    }
+}
+
+/// The [RefreshTokenStorage] that is used by the [routes](crate::routes()).
+/// Manage an instance with [rocket] to enable refresh tokens.
+pub struct RefreshTokenStore {
+    storage: Box<dyn RefreshTokenStorage + Send + Sync>,
+}
+
+impl RefreshTokenStore {
+    /// Creates a new instance using the given storage.
+    pub fn new<RTS>(storage: RTS) -> Self
+    where
+        RTS: RefreshTokenStorage + Send + Sync + 'static,
+    {
+        Self {
+            storage: Box::new(storage),
+        }
+    }
+}
+
+impl RefreshTokenStorage for RefreshTokenStore {
+    fn lifetime(&self) -> TimeDelta {
+        self.storage.lifetime()
+    }
+    fn refresh_token(
+        &self,
+        token: &str,
+    ) -> Result<Option<RefreshToken>, Error> {
+        self.storage.refresh_token(token)
+    }
+    fn store_refresh_token(
+        &self,
+        refresh_token: RefreshToken,
+    ) -> Result<(), Error> {
+        self.storage.store_refresh_token(refresh_token)
+    }
+    fn use_refresh_token(
+        &self,
+        token: &str,
+    ) -> Result<Option<RefreshToken>, Error> {
+        self.storage.use_refresh_token(token)
+    }
+    fn revoke_family(&self, family: &str) -> Result<(), Error> {
+        self.storage.revoke_family(family)
+    }
+}
+
+/// Generates a random token.
+fn generate_token() -> String {
+    use rand::{
+        distributions::Alphanumeric,
+        thread_rng,
+        Rng,
+    };
+
+    thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(48)
+        .map(char::from)
+        .collect()
+}
+
+/// Creates the error for a refresh token that is rejected.
+fn invalid(reason: &str) -> Error {
+    Error::InvalidToken(reason.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotation_issues_token_of_same_family() {
+        let storage = MemoryRefreshTokenStorage::default();
+        let issued = storage.issue_refresh_token("cosmo").unwrap();
+        let rotated = storage.rotate_refresh_token(&issued.token).unwrap();
+        assert_ne!(rotated.token, issued.token);
+        assert_eq!(rotated.family, issued.family);
+        assert_eq!(rotated.passport_id, "cosmo");
+        assert!(storage.refresh_token(&issued.token).unwrap().unwrap().used);
+    }
+
+    #[test]
+    fn reuse_revokes_family() {
+        let storage = MemoryRefreshTokenStorage::default();
+        let issued = storage.issue_refresh_token("cosmo").unwrap();
+        let other = storage.issue_refresh_token("cosmo").unwrap();
+        let rotated = storage.rotate_refresh_token(&issued.token).unwrap();
+
+        let reused = storage.rotate_refresh_token(&issued.token);
+        assert!(matches!(reused, Err(Error::InvalidToken(_))));
+        assert!(storage.refresh_token(&rotated.token).unwrap().is_none());
+        let revoked = storage.rotate_refresh_token(&rotated.token);
+        assert!(matches!(revoked, Err(Error::InvalidToken(_))));
+
+        // Other families are not affected.
+        assert!(storage.rotate_refresh_token(&other.token).is_ok());
+    }
+
+    #[test]
+    fn revoked_family_can_not_be_refreshed() {
+        let storage = MemoryRefreshTokenStorage::default();
+        let issued = storage.issue_refresh_token("cosmo").unwrap();
+        let rotated = storage.rotate_refresh_token(&issued.token).unwrap();
+        storage.revoke_refresh_token(&rotated.token).unwrap();
+        for token in [&issued.token, &rotated.token] {
+            let result = storage.rotate_refresh_token(token);
+            assert!(matches!(result, Err(Error::InvalidToken(_))));
+        }
+    }
+
+    #[test]
+    fn unknown_and_expired_tokens_are_rejected() {
+        let storage = MemoryRefreshTokenStorage::new(TimeDelta::seconds(-1));
+        let result = storage.rotate_refresh_token("unknown");
+        assert!(matches!(result, Err(Error::InvalidToken(_))));
+        let expired = storage.issue_refresh_token("cosmo").unwrap();
+        let result = storage.rotate_refresh_token(&expired.token);
+        assert!(matches!(result, Err(Error::InvalidToken(_))));
+    }
+}
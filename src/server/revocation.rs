@@ -0,0 +1,169 @@
+//! Server side revocation of a [BoardingPass](super::boarding_pass::BoardingPass)
+//! before it expires.
+//!
+//! To enable revocation, manage a [RevocationList] with [rocket]. The request
+//! guards reject every token that has been revoked, for example by
+//! [Gate::logout](super::gate::Gate::logout).
+//!
+//! ```rust,ignore
+//! rocket::build()
+//!     .manage(RevocationList::new(MemoryRevocationStorage::default()))
+//! ```
+use super::{
+    boarding_pass::payloads::JsonWebToken,
+    error::Error,
+};
+use chrono::{
+    DateTime,
+    TimeDelta,
+    Utc,
+};
+use std::{
+    collections::HashMap,
+    sync::RwLock,
+};
+
+/// Storage of revoked boarding pass data of type `BPD`.
+pub trait RevocationStorage<BPD> {
+    /// Revokes the given boarding pass data.
+    fn revoke(&self, data: &BPD) -> Result<(), Error>;
+    /// Returns `true` if the given boarding pass data has been revoked.
+    fn is_revoked(&self, data: &BPD) -> Result<bool, Error>;
+}
+
+/// A [RevocationStorage] where the [jti](JsonWebToken::jti) of revoked tokens
+/// is stored in memory.
+///
+/// An entry is only kept until the token expires plus the leeway, as it is
+/// rejected anyway afterwards. The leeway needs to be at least the
+/// [leeway](super::ciphering::ValidationOptions::leeway) used for decoding.
+/// Expired entries are removed on every revocation.
+pub struct MemoryRevocationStorage {
+    revoked: RwLock<HashMap<String, DateTime<Utc>>>,
+    leeway: TimeDelta,
+}
+
+impl Default for MemoryRevocationStorage {
+    /// Default implementation:
+    ///
+    /// - Leeway: `60` seconds, matching the default
+    ///   [ValidationOptions](super::ciphering::ValidationOptions)
+    fn default() -> Self {
+        Self {
+            revoked: RwLock::new(HashMap::new()),
+            leeway: TimeDelta::seconds(60),
+        }
+    }
+}
+
+impl MemoryRevocationStorage {
+    /// Sets the time an entry is kept after its token expired.
+    pub fn with_leeway(mut self, leeway: TimeDelta) -> Self {
+        self.leeway = leeway;
+        self
+    }
+
+    /// Removes all entries whose token has expired, including the leeway.
+    pub fn prune(&self) -> Result<(), Error> {
+        let oldest = Utc::now() - self.leeway;
+        self.revoked
+            .write()
+            .map_err(|e| {
+                Error::storage(format!("Could not write revocations: {e}"))
+            })?
+            .retain(|_, expires_at| *expires_at > oldest);
+        Ok(())
+    }
+}
+
+impl RevocationStorage<JsonWebToken> for MemoryRevocationStorage {
+    fn revoke(&self, data: &JsonWebToken) -> Result<(), Error> {
+        let Some(expires_at) = DateTime::from_timestamp(data.exp() as i64, 0)
+        else {
+            return Err(Error::InvalidToken("Invalid exp claim.".to_string()));
+        };
+        self.prune()?;
+        self.revoked
+            .write()
+            .map_err(|e| {
+                Error::storage(format!("Could not write revocations: {e}"))
+            })?
+            .insert(data.jti.clone(), expires_at);
+        Ok(())
+    }
+    fn is_revoked(&self, data: &JsonWebToken) -> Result<bool, Error> {
+        Ok(self
+            .revoked
+            .read()
+            .map_err(|e| {
+                Error::storage(format!("Could not read revocations: {e}"))
+            })?
+            .contains_key(&data.jti))
+    }
+}
+
+/// The [RevocationStorage] that is consulted by the request guards. Manage an
+/// instance with [rocket] to enable revocation.
+pub struct RevocationList {
+    storage: Box<dyn RevocationStorage<JsonWebToken> + Send + Sync>,
+}
+
+impl RevocationList {
+    /// Creates a new instance using the given storage.
+    pub fn new<RS>(storage: RS) -> Self
+    where
+        RS: RevocationStorage<JsonWebToken> + Send + Sync + 'static,
+    {
+        Self {
+            storage: Box::new(storage),
+        }
+    }
+}
+
+impl RevocationStorage<JsonWebToken> for RevocationList {
+    fn revoke(&self, data: &JsonWebToken) -> Result<(), Error> {
+        self.storage.revoke(data)
+    }
+    fn is_revoked(&self, data: &JsonWebToken) -> Result<bool, Error> {
+        self.storage.is_revoked(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Creates claims that expire the given number of seconds from now.
+    fn token(jti: &str, expires_in: i64) -> JsonWebToken {
+        let now = Utc::now().timestamp();
+        JsonWebToken::for_subject(
+            "cosmo",
+            jti,
+            now as usize,
+            (now + expires_in) as usize,
+        )
+    }
+
+    #[test]
+    fn revoked_tokens_are_rejected() {
+        let revocations =
+            RevocationList::new(MemoryRevocationStorage::default());
+        let revoked = token("revoked", 60);
+        revocations.revoke(&revoked).unwrap();
+        assert!(revocations.is_revoked(&revoked).unwrap());
+        assert!(!revocations.is_revoked(&token("other", 60)).unwrap());
+    }
+
+    #[test]
+    fn prune_keeps_entries_within_leeway() {
+        let storage = MemoryRevocationStorage::default()
+            .with_leeway(TimeDelta::seconds(30));
+        let within_leeway = token("within_leeway", -10);
+        let beyond_leeway = token("beyond_leeway", -60);
+        storage.revoke(&within_leeway).unwrap();
+        storage.revoke(&beyond_leeway).unwrap();
+        storage.prune().unwrap();
+        assert!(storage.is_revoked(&within_leeway).unwrap());
+        assert!(!storage.is_revoked(&beyond_leeway).unwrap());
+    }
+}
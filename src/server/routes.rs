@@ -0,0 +1,641 @@
+//! Prebuilt routes for login, logout, token refresh and the current passport.
+//!
+//! ```rust,ignore
+//! rocket::build()
+//!     .manage(JwtCipher::random())
+//!     .manage(MemoryPassportRegister::from(passports))
+//!     .manage(RefreshTokenStore::new(MemoryRefreshTokenStorage::default()))
+//!     .mount("/auth", cosmodrome::routes::<JwtBearerGate, MemoryPassportRegister>())
+//!     .register("/", cosmodrome::problem::catchers())
+//! ```
+//!
+//! | Route           | Request                            | Success          | Body            |
+//! |-----------------|------------------------------------|------------------|-----------------|
+//! | `POST /login`   | [Ticket] as JSON or form           | `200 OK`         | [TokenResponse] |
+//! | `POST /refresh` | [RefreshRequest] as JSON or form   | `200 OK`         | [TokenResponse] |
+//! | `POST /logout`  | [BoardingPass], [RefreshRequest]   | `204 No Content` |                 |
+//! | `GET /me`       | [BoardingPass]                     | `200 OK`         | [PassportView]  |
+//!
+//! Failures are responded with the corresponding [Error]. Refresh tokens are
+//! only issued if a [RefreshTokenStore] is managed by [rocket], otherwise
+//! `/refresh` is not available. On logout, the [BoardingPass] is added to the
+//! [RevocationList], if managed. If the logout request carries a
+//! [RefreshRequest] as JSON or form, the family of its refresh token is
+//! revoked as well.
+use super::{
+    asynchronous::{
+        AsyncGate,
+        AsyncPassportRegister,
+    },
+    auth_type::{
+        AuthType,
+        Bearer,
+        Cookie,
+        Session,
+    },
+    boarding_pass::{
+        payloads::JsonWebToken,
+        BoardingPass,
+    },
+    ciphering::{
+        JwtCipher,
+        JwtKeyring,
+    },
+    error::Error,
+    gate::{
+        Gate,
+        JwtBearerGate,
+        JwtCookieGate,
+        SessionGate,
+    },
+    passport::{
+        Passport,
+        PassportType,
+    },
+    refresh::{
+        RefreshTokenStorage,
+        RefreshTokenStore,
+    },
+    revocation::RevocationList,
+    session::SessionStore,
+    storage::{
+        BoardingPassStorage,
+        CookieStorageOptions,
+        Storage,
+    },
+    traveler::Traveler,
+};
+use crate::Ticket;
+use chrono::{
+    DateTime,
+    Utc,
+};
+use rocket::{
+    data::{
+        self,
+        FromData,
+    },
+    form::Form,
+    http::{
+        CookieJar,
+        Method,
+        Status,
+    },
+    request::{
+        self,
+        FromRequest,
+    },
+    route::{
+        BoxFuture,
+        Outcome,
+    },
+    serde::{
+        json::Json,
+        Deserialize,
+        Serialize,
+    },
+    Data,
+    FromForm,
+    Request,
+    Route,
+};
+use std::fmt::Display;
+
+/// A [Gate] that can be mounted using [routes].
+pub trait MountableGate: Send + Sync + 'static {
+    /// The [AuthType] of the [BoardingPass]es issued by this gate.
+    type AuthType: AuthType + Send + Sync + 'static;
+    /// The identifier that is used to remove a [BoardingPass] from the
+    /// storage.
+    type Identifier: Send;
+    /// The storage that is created for each request.
+    type Storage<'r>: BoardingPassStorage<
+            JsonWebToken,
+            Self::AuthType,
+            Self::Identifier,
+            String,
+        > + Send
+        + Sync;
+    /// Whether the encoded [BoardingPass] is returned in the response body.
+    /// Gates that store it in a cookie do not expose it.
+    const EXPOSES_TOKEN: bool;
+
+    /// Creates the storage for the given request.
+    fn storage<'r>(
+        request: &'r Request<'_>,
+    ) -> Result<Self::Storage<'r>, Error>;
+    /// Returns the identifier of the given [BoardingPass].
+    fn identifier(
+        boarding_pass: &BoardingPass<JsonWebToken, Self::AuthType>,
+    ) -> Self::Identifier;
+}
+
+impl MountableGate for JwtBearerGate {
+    type AuthType = Bearer;
+    type Identifier = ();
+    type Storage<'r> = Storage<(), (), JsonWebToken, Bearer, JwtCipher, String>;
+    const EXPOSES_TOKEN: bool = true;
+
+    fn storage<'r>(
+        request: &'r Request<'_>,
+    ) -> Result<Self::Storage<'r>, Error> {
+        Ok(Storage::new((), (), cipher(request)?))
+    }
+    fn identifier(_boarding_pass: &BoardingPass<JsonWebToken, Bearer>) {}
+}
+
+impl MountableGate for JwtCookieGate {
+    type AuthType = Cookie;
+    type Identifier = ();
+    type Storage<'r> = Storage<
+        &'r CookieJar<'r>,
+        CookieStorageOptions<'static>,
+        JsonWebToken,
+        Cookie,
+        JwtCipher,
+        String,
+    >;
+    const EXPOSES_TOKEN: bool = false;
+
+    fn storage<'r>(
+        request: &'r Request<'_>,
+    ) -> Result<Self::Storage<'r>, Error> {
+        Ok(Storage::new(
+            request.cookies(),
+            cookie_storage_options(request),
+            cipher(request)?,
+        ))
+    }
+    fn identifier(_boarding_pass: &BoardingPass<JsonWebToken, Cookie>) {}
+}
+
+impl MountableGate for SessionGate {
+    type AuthType = Session;
+    type Identifier = String;
+    type Storage<'r> = Storage<
+        (&'r CookieJar<'r>, &'r SessionStore),
+        CookieStorageOptions<'static>,
+        JsonWebToken,
+        Session,
+        JwtCipher,
+        String,
+    >;
+    const EXPOSES_TOKEN: bool = false;
+
+    /// Sessions are not encoded, so the cipher is only used to issue the
+    /// claims. A random one is used if none is managed.
+    fn storage<'r>(
+        request: &'r Request<'_>,
+    ) -> Result<Self::Storage<'r>, Error> {
+        let Some(sessions) = request.rocket().state::<SessionStore>() else {
+            return Err(Error::Configuration(
+                "No SessionStore is managed by rocket.".to_string(),
+            ));
+        };
+        Ok(Storage::new(
+            (request.cookies(), sessions),
+            cookie_storage_options(request),
+            cipher(request).unwrap_or_else(|_| JwtCipher::random()),
+        ))
+    }
+    fn identifier(
+        boarding_pass: &BoardingPass<JsonWebToken, Session>,
+    ) -> String {
+        boarding_pass.data.jti.clone()
+    }
+}
+
+/// The response of `/login` and `/refresh`.
+#[derive(Serialize, Clone, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct TokenResponse {
+    /// The encoded [BoardingPass], if the gate
+    /// [exposes](MountableGate::EXPOSES_TOKEN) it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub access_token: Option<String>,
+    /// The refresh token, if a [RefreshTokenStore] is managed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refresh_token: Option<String>,
+}
+
+/// The request body of `/refresh`.
+#[derive(Deserialize, FromForm, Clone, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct RefreshRequest {
+    /// The refresh token that is exchanged.
+    pub refresh_token: String,
+}
+
+/// The form variant of a [Ticket].
+#[derive(FromForm)]
+struct TicketForm {
+    id: String,
+    secret: String,
+}
+
+/// The [Passport] as returned by `/me`, without its password.
+#[derive(Serialize, Clone, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct PassportView {
+    /// See [Passport::id].
+    pub id: String,
+    /// See [Passport::services].
+    pub services: Vec<String>,
+    /// See [Passport::account_type].
+    pub account_type: PassportType,
+    /// See [Passport::confirmed].
+    pub confirmed: bool,
+    /// See [Passport::expires_at].
+    pub expires_at: DateTime<Utc>,
+}
+
+impl From<&Passport> for PassportView {
+    fn from(value: &Passport) -> Self {
+        Self {
+            id: value.id.clone(),
+            services: value.services().to_vec(),
+            account_type: value.account_type.clone(),
+            confirmed: value.confirmed,
+            expires_at: value.expires_at,
+        }
+    }
+}
+
+/// Returns the routes for the gate `G` using the [AsyncPassportRegister] `PR`.
+/// Both, `PR` and a cipher need to be managed by [rocket].
+///
+/// Implemented as plain handlers, so rocket's sentinels are not triggered.
+/// Attach the [Preflight](super::preflight::Preflight) fairing to validate
+/// the setup.
+pub fn routes<G, PR>() -> Vec<Route>
+where
+    G: MountableGate
+        + Gate<JsonWebToken, G::AuthType, G::Identifier, String>
+        + AsyncGate<JsonWebToken, G::AuthType, G::Identifier, String>,
+    PR: AsyncPassportRegister + 'static,
+    BoardingPass<JsonWebToken, G::AuthType>:
+        for<'r> FromRequest<'r, Error = Error>,
+{
+    vec![
+        Route::new(Method::Post, "/login", login::<G, PR>),
+        Route::new(Method::Post, "/refresh", refresh::<G, PR>),
+        Route::new(Method::Post, "/logout", logout::<G>),
+        Route::new(Method::Get, "/me", me::<G, PR>),
+    ]
+}
+
+/// Checks in the [Ticket] of the request and boards the [Passport].
+fn login<'r, G, PR>(request: &'r Request<'_>, data: Data<'r>) -> BoxFuture<'r>
+where
+    G: MountableGate
+        + Gate<JsonWebToken, G::AuthType, G::Identifier, String>
+        + AsyncGate<JsonWebToken, G::AuthType, G::Identifier, String>,
+    PR: AsyncPassportRegister + 'static,
+{
+    Box::pin(async move {
+        let ticket = if request.content_type().is_some_and(|c| c.is_form()) {
+            body(Form::<TicketForm>::from_data(request, data).await)
+                .map(|f| Ticket::new(&f.id, &f.secret))
+        } else {
+            body(Json::<Ticket>::from_data(request, data).await)
+                .map(Json::into_inner)
+        };
+        let result = match ticket {
+            Ok(ticket) => board::<G, PR>(request, ticket).await,
+            Err(e) => Err(e),
+        };
+        Outcome::from(request, result)
+    })
+}
+
+/// Exchanges the refresh token of the request for a new [BoardingPass].
+fn refresh<'r, G, PR>(request: &'r Request<'_>, data: Data<'r>) -> BoxFuture<'r>
+where
+    G: MountableGate
+        + AsyncGate<JsonWebToken, G::AuthType, G::Identifier, String>,
+    PR: AsyncPassportRegister + 'static,
+{
+    Box::pin(async move {
+        let Some(refresh_tokens) =
+            request.rocket().state::<RefreshTokenStore>()
+        else {
+            return Outcome::Forward((data, Status::NotFound));
+        };
+        let refresh_request =
+            if request.content_type().is_some_and(|c| c.is_form()) {
+                body(Form::<RefreshRequest>::from_data(request, data).await)
+                    .map(Form::into_inner)
+            } else {
+                body(Json::<RefreshRequest>::from_data(request, data).await)
+                    .map(Json::into_inner)
+            };
+        let result = match refresh_request {
+            Ok(r) => {
+                exchange::<G, PR>(request, &r.refresh_token, refresh_tokens)
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+        Outcome::from(request, result)
+    })
+}
+
+/// Removes and revokes the [BoardingPass] of the request, together with the
+/// family of the refresh token in the body, if any.
+fn logout<'r, G>(request: &'r Request<'_>, data: Data<'r>) -> BoxFuture<'r>
+where
+    G: MountableGate + Gate<JsonWebToken, G::AuthType, G::Identifier, String>,
+    BoardingPass<JsonWebToken, G::AuthType>:
+        for<'x> FromRequest<'x, Error = Error>,
+{
+    Box::pin(async move {
+        let boarding_pass = match request
+            .guard::<BoardingPass<JsonWebToken, G::AuthType>>()
+            .await
+        {
+            request::Outcome::Success(b) => b,
+            request::Outcome::Error((_, e)) => {
+                return Outcome::from(request, e)
+            }
+            request::Outcome::Forward(s) => return Outcome::Forward((data, s)),
+        };
+        let mut data = data;
+        let refresh_request = if data.peek(1).await.is_empty() {
+            Ok(None)
+        } else if request.content_type().is_some_and(|c| c.is_form()) {
+            body(Form::<RefreshRequest>::from_data(request, data).await)
+                .map(|f| Some(f.into_inner()))
+        } else {
+            body(Json::<RefreshRequest>::from_data(request, data).await)
+                .map(|j| Some(j.into_inner()))
+        };
+        let result = refresh_request
+            .and_then(|r| match r {
+                Some(r) => forget(request, &boarding_pass, &r.refresh_token),
+                None => Ok(()),
+            })
+            .and_then(|()| leave::<G>(request, &boarding_pass));
+        Outcome::from(request, result.map(|()| Status::NoContent))
+    })
+}
+
+/// Responds with the [PassportView] of the [Traveler].
+fn me<'r, G, PR>(request: &'r Request<'_>, data: Data<'r>) -> BoxFuture<'r>
+where
+    G: MountableGate,
+    PR: AsyncPassportRegister + 'static,
+    BoardingPass<JsonWebToken, G::AuthType>:
+        for<'x> FromRequest<'x, Error = Error>,
+{
+    Box::pin(async move {
+        match request.guard::<Traveler<G::AuthType, PR>>().await {
+            request::Outcome::Success(traveler) => Outcome::from(
+                request,
+                Json(PassportView::from(&traveler.passport)),
+            ),
+            request::Outcome::Error((_, e)) => Outcome::from(request, e),
+            request::Outcome::Forward(s) => Outcome::Forward((data, s)),
+        }
+    })
+}
+
+/// Logs in using the given [Ticket]. An unknown passport is reported as
+/// [Error::InvalidCredentials] to not disclose which passports exist.
+///
+/// The [Ticket] is checked in using the [AsyncGate], so verifying the
+/// password does not block other requests.
+async fn board<G, PR>(
+    request: &Request<'_>,
+    ticket: Ticket,
+) -> Result<Json<TokenResponse>, Error>
+where
+    G: MountableGate
+        + Gate<JsonWebToken, G::AuthType, G::Identifier, String>
+        + AsyncGate<JsonWebToken, G::AuthType, G::Identifier, String>,
+    PR: AsyncPassportRegister + 'static,
+{
+    let register = register::<PR>(request)?;
+    let passport = <G as AsyncGate<_, _, _, _>>::check_in(&ticket, register)
+        .await
+        .map_err(|e| match e {
+            Error::PassportNotFound(_) => Error::InvalidCredentials,
+            e => e,
+        })?;
+    let storage = G::storage(request)?;
+    let access_token = <G as Gate<_, _, _, _>>::board(&passport, &storage)?;
+    let refresh_token = match request.rocket().state::<RefreshTokenStore>() {
+        Some(refresh_tokens) => {
+            Some(refresh_tokens.issue_refresh_token(&passport.id)?.token)
+        }
+        None => None,
+    };
+    Ok(Json(TokenResponse {
+        access_token: G::EXPOSES_TOKEN.then_some(access_token),
+        refresh_token,
+    }))
+}
+
+/// Exchanges the given refresh token.
+async fn exchange<G, PR>(
+    request: &Request<'_>,
+    refresh_token: &str,
+    refresh_tokens: &RefreshTokenStore,
+) -> Result<Json<TokenResponse>, Error>
+where
+    G: MountableGate
+        + AsyncGate<JsonWebToken, G::AuthType, G::Identifier, String>,
+    PR: AsyncPassportRegister + 'static,
+{
+    let register = register::<PR>(request)?;
+    let storage = G::storage(request)?;
+    let token_pair = <G as AsyncGate<_, _, _, _>>::refresh(
+        refresh_token,
+        register,
+        &storage,
+        refresh_tokens,
+    )
+    .await?;
+    Ok(Json(TokenResponse {
+        access_token: G::EXPOSES_TOKEN.then_some(token_pair.access_token),
+        refresh_token: Some(token_pair.refresh_token),
+    }))
+}
+
+/// Converts the outcome of parsing the request body, rejecting a missing or
+/// malformed body with [Error::BadRequest].
+fn body<T, E>(outcome: data::Outcome<'_, T, E>) -> Result<T, Error>
+where
+    E: Display,
+{
+    match outcome {
+        data::Outcome::Success(value) => Ok(value),
+        data::Outcome::Error((_, e)) => Err(Error::BadRequest(e.to_string())),
+        data::Outcome::Forward(_) => {
+            Err(Error::BadRequest("Unsupported request body.".to_string()))
+        }
+    }
+}
+
+/// Removes the given [BoardingPass] from the storage and revokes it if a
+/// [RevocationList] is managed.
+fn leave<G>(
+    request: &Request<'_>,
+    boarding_pass: &BoardingPass<JsonWebToken, G::AuthType>,
+) -> Result<(), Error>
+where
+    G: MountableGate + Gate<JsonWebToken, G::AuthType, G::Identifier, String>,
+{
+    let storage = G::storage(request)?;
+    let identifier = G::identifier(boarding_pass);
+    match request.rocket().state::<RevocationList>() {
+        Some(revocations) => {
+            G::logout(identifier, boarding_pass, &storage, revocations)
+        }
+        None => storage.remove_boarding_pass(identifier),
+    }
+}
+
+/// Revokes the family of the given refresh token if a [RefreshTokenStore] is
+/// managed. Tokens of other passports are rejected, unknown ones are ignored.
+fn forget<AT>(
+    request: &Request<'_>,
+    boarding_pass: &BoardingPass<JsonWebToken, AT>,
+    refresh_token: &str,
+) -> Result<(), Error>
+where
+    AT: AuthType,
+{
+    let Some(refresh_tokens) = request.rocket().state::<RefreshTokenStore>()
+    else {
+        return Ok(());
+    };
+    match refresh_tokens.refresh_token(refresh_token)? {
+        Some(r) if r.passport_id != boarding_pass.data.sub => {
+            Err(Error::InvalidToken(
+                "Refresh token of another passport.".to_string(),
+            ))
+        }
+        Some(r) => refresh_tokens.revoke_family(&r.family),
+        None => Ok(()),
+    }
+}
+
+/// Returns the managed [PassportRegister] `PR`.
+fn register<'r, PR>(request: &'r Request<'_>) -> Result<&'r PR, Error>
+where
+    PR: AsyncPassportRegister + 'static,
+{
+    request.rocket().state::<PR>().ok_or_else(|| {
+        Error::Configuration(format!(
+            "No PassportRegister of type {} is managed by rocket.",
+            std::any::type_name::<PR>()
+        ))
+    })
+}
+
+/// Returns the managed [JwtCipher], or the active one of the managed
+/// [JwtKeyring].
+fn cipher(request: &Request<'_>) -> Result<JwtCipher, Error> {
+    let rocket = request.rocket();
+    if let Some(cipher) = rocket.state::<JwtCipher>() {
+        return Ok(cipher.clone());
+    }
+    if let Some(keyring) = rocket.state::<JwtKeyring>() {
+        return Ok(keyring.active().clone());
+    }
+    Err(Error::Configuration(
+        "No JwtCipher or JwtKeyring is managed by rocket.".to_string(),
+    ))
+}
+
+/// Returns a copy of the managed [CookieStorageOptions], or the default ones.
+fn cookie_storage_options(
+    request: &Request<'_>,
+) -> CookieStorageOptions<'static> {
+    match request.rocket().state::<CookieStorageOptions>() {
+        Some(options) => {
+            CookieStorageOptions::new(options.cookie_template.clone())
+        }
+        None => CookieStorageOptions::default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::{
+        passport_register::MemoryPassportRegister,
+        refresh::MemoryRefreshTokenStorage,
+    };
+    use rocket::{
+        http::Header,
+        local::blocking::Client,
+        serde::json::{
+            json,
+            Value,
+        },
+    };
+
+    #[test]
+    fn logout_revokes_refresh_token() {
+        let mut passport =
+            Passport::new("cosmo", "secret", &[], PassportType::User).unwrap();
+        passport.confirmed = true;
+        let rocket = rocket::build()
+            .manage(JwtCipher::random())
+            .manage(MemoryPassportRegister::from(vec![passport]))
+            .manage(
+                RefreshTokenStore::new(MemoryRefreshTokenStorage::default()),
+            )
+            .mount(
+                "/",
+                super::routes::<JwtBearerGate, MemoryPassportRegister>(),
+            );
+        let client = Client::tracked(rocket).unwrap();
+        let login = |client: &Client| {
+            let tokens = client
+                .post("/login")
+                .json(&json!({ "id": "cosmo", "secret": "secret" }))
+                .dispatch()
+                .into_json::<Value>()
+                .unwrap();
+            (
+                tokens["access_token"].as_str().unwrap().to_string(),
+                tokens["refresh_token"].as_str().unwrap().to_string(),
+            )
+        };
+        let refresh = |client: &Client, refresh_token: &str| {
+            client
+                .post("/refresh")
+                .json(&json!({ "refresh_token": refresh_token }))
+                .dispatch()
+                .status()
+        };
+
+        let (access_token, refresh_token) = login(&client);
+        let status = client
+            .post("/logout")
+            .header(Header::new(
+                "Authorization",
+                format!("Bearer {access_token}"),
+            ))
+            .json(&json!({ "refresh_token": refresh_token }))
+            .dispatch()
+            .status();
+        assert_eq!(status, Status::NoContent);
+        assert_eq!(refresh(&client, &refresh_token), Status::Unauthorized);
+
+        // without a refresh token, only the access token is discarded
+        let (access_token, refresh_token) = login(&client);
+        let status = client
+            .post("/logout")
+            .header(Header::new(
+                "Authorization",
+                format!("Bearer {access_token}"),
+            ))
+            .dispatch()
+            .status();
+        assert_eq!(status, Status::NoContent);
+        assert_eq!(refresh(&client, &refresh_token), Status::Ok);
+    }
+}
@@ -0,0 +1,136 @@
+//! Server side sessions for the [Session](super::auth_type::Session) [AuthType](super::auth_type::AuthType).
+//!
+//! The cookie only carries an opaque session id, which equals the
+//! [jti](JsonWebToken::jti) of the stored claims. The claims themselves stay
+//! on the server in a [SessionBackend], so a session ends as soon as it is
+//! removed from the backend.
+//!
+//! ```rust,ignore
+//! rocket::build()
+//!     .manage(SessionStore::new(MemorySessionBackend::default()))
+//! ```
+use super::{
+    boarding_pass::payloads::JsonWebToken,
+    error::Error,
+    revocation::RevocationStorage,
+};
+use std::{
+    collections::HashMap,
+    sync::RwLock,
+};
+
+/// Backend that stores the boarding pass data of type `BPD` by session id.
+pub trait SessionBackend<BPD> {
+    /// Returns the data of the given session. Expired sessions may be
+    /// returned, the request guard rejects them.
+    fn session(&self, session_id: &str) -> Result<Option<BPD>, Error>;
+    /// Stores the data of the given session.
+    fn store_session(&self, session_id: &str, data: BPD) -> Result<(), Error>;
+    /// Removes the given session.
+    fn remove_session(&self, session_id: &str) -> Result<(), Error>;
+}
+
+/// A [SessionBackend] where all sessions are stored in memory.
+///
+/// Expired sessions are removed on every new session.
+#[derive(Default)]
+pub struct MemorySessionBackend {
+    sessions: RwLock<HashMap<String, JsonWebToken>>,
+}
+
+impl MemorySessionBackend {
+    /// Removes all expired sessions.
+    pub fn prune(&self) -> Result<(), Error> {
+        self.sessions
+            .write()
+            .map_err(|e| {
+                Error::storage(format!("Could not write sessions: {e}"))
+            })?
+            .retain(|_, data| data.is_valid());
+        Ok(())
+    }
+}
+
+impl SessionBackend<JsonWebToken> for MemorySessionBackend {
+    fn session(&self, session_id: &str) -> Result<Option<JsonWebToken>, Error> {
+        Ok(self
+            .sessions
+            .read()
+            .map_err(|e| {
+                Error::storage(format!("Could not read sessions: {e}"))
+            })?
+            .get(session_id)
+            .filter(|data| data.is_valid())
+            .cloned())
+    }
+    fn store_session(
+        &self,
+        session_id: &str,
+        data: JsonWebToken,
+    ) -> Result<(), Error> {
+        self.prune()?;
+        self.sessions
+            .write()
+            .map_err(|e| {
+                Error::storage(format!("Could not write sessions: {e}"))
+            })?
+            .insert(session_id.to_string(), data);
+        Ok(())
+    }
+    fn remove_session(&self, session_id: &str) -> Result<(), Error> {
+        self.sessions
+            .write()
+            .map_err(|e| {
+                Error::storage(format!("Could not write sessions: {e}"))
+            })?
+            .remove(session_id);
+        Ok(())
+    }
+}
+
+/// The [SessionBackend] that is used by the request guard of the
+/// [Session](super::auth_type::Session) [AuthType](super::auth_type::AuthType).
+/// Manage an instance with [rocket] to enable sessions.
+///
+/// Removing a session revokes it, so the store can be passed to
+/// [Gate::logout](super::gate::Gate::logout) as [RevocationStorage].
+pub struct SessionStore {
+    backend: Box<dyn SessionBackend<JsonWebToken> + Send + Sync>,
+}
+
+impl SessionStore {
+    /// Creates a new instance using the given backend.
+    pub fn new<SB>(backend: SB) -> Self
+    where
+        SB: SessionBackend<JsonWebToken> + Send + Sync + 'static,
+    {
+        Self {
+            backend: Box::new(backend),
+        }
+    }
+}
+
+impl SessionBackend<JsonWebToken> for SessionStore {
+    fn session(&self, session_id: &str) -> Result<Option<JsonWebToken>, Error> {
+        self.backend.session(session_id)
+    }
+    fn store_session(
+        &self,
+        session_id: &str,
+        data: JsonWebToken,
+    ) -> Result<(), Error> {
+        self.backend.store_session(session_id, data)
+    }
+    fn remove_session(&self, session_id: &str) -> Result<(), Error> {
+        self.backend.remove_session(session_id)
+    }
+}
+
+impl RevocationStorage<JsonWebToken> for SessionStore {
+    fn revoke(&self, data: &JsonWebToken) -> Result<(), Error> {
+        self.remove_session(&data.jti)
+    }
+    fn is_revoked(&self, data: &JsonWebToken) -> Result<bool, Error> {
+        Ok(self.session(&data.jti)?.is_none())
+    }
+}
@@ -0,0 +1,607 @@
+//! A [SqlitePassportRegister] that stores the passports in a SQLite
+//! database using [sqlx].
+//!
+//! The schema is created by the migrations in the `migrations` folder of this
+//! crate. The statements are kept compatible with Postgres.
+//!
+//! The register takes its pool from a [rocket_db_pools] [Database], attach the
+//! [PassportDatabase] fairing after initializing it:
+//!
+//! ```rust,ignore
+//! #[derive(Database)]
+//! #[database("passports")]
+//! struct Passports(sqlx::SqlitePool);
+//!
+//! rocket::build()
+//!     .attach(Passports::init())
+//!     .attach(PassportDatabase::<Passports>::new())
+//!     .mount("/auth", cosmodrome::routes::<JwtBearerGate, SqlitePassportRegister>())
+//! ```
+use super::{
+    asynchronous::AsyncPassportRegister,
+    error::Error,
+    passport::{
+        Passport,
+        PassportStatusPolicy,
+        PassportType,
+    },
+    passport_register::check_id,
+};
+use chrono::{
+    DateTime,
+    Utc,
+};
+use log::error;
+use rocket::{
+    fairing::{
+        self,
+        Fairing,
+        Info,
+        Kind,
+    },
+    Build,
+    Rocket,
+};
+use rocket_db_pools::Database;
+use sqlx::{
+    migrate::Migrator,
+    sqlite::SqliteRow,
+    Row,
+    SqliteConnection,
+    SqlitePool,
+};
+use std::{
+    collections::HashMap,
+    marker::PhantomData,
+};
+
+/// The migrations that create the schema of the [SqlitePassportRegister].
+pub static MIGRATOR: Migrator = sqlx::migrate!();
+
+/// An [AsyncPassportRegister] where all [Passport]s are stored in a SQLite
+/// database.
+///
+/// Custom [PassportType]s are added to the `passport_types` table when a
+/// passport using them is stored. They are prefixed with `custom:`, so a
+/// custom type named like a built-in one is not loaded as the built-in.
+#[derive(Clone, Debug)]
+pub struct SqlitePassportRegister {
+    pool: SqlitePool,
+    status_policy: PassportStatusPolicy,
+}
+
+impl SqlitePassportRegister {
+    /// Creates a new instance using the given pool. The schema needs to be
+    /// created using [migrate](Self::migrate) before first use.
+    pub fn new(pool: SqlitePool) -> Self {
+        Self {
+            pool,
+            status_policy: PassportStatusPolicy::default(),
+        }
+    }
+
+    /// Sets the [PassportStatusPolicy] of this register.
+    pub fn with_status_policy(
+        mut self,
+        status_policy: PassportStatusPolicy,
+    ) -> Self {
+        self.status_policy = status_policy;
+        self
+    }
+
+    /// Returns the underlying pool.
+    pub fn pool(&self) -> &SqlitePool {
+        &self.pool
+    }
+
+    /// Runs all pending [migrations](MIGRATOR).
+    pub async fn migrate(&self) -> Result<(), Error> {
+        MIGRATOR.run(&self.pool).await.map_err(Error::storage)
+    }
+}
+
+impl AsyncPassportRegister for SqlitePassportRegister {
+    async fn passport(
+        &self,
+        passport_id: &str,
+    ) -> Result<Option<Passport>, Error> {
+        let mut connection =
+            self.pool.acquire().await.map_err(Error::storage)?;
+        fetch(&mut connection, passport_id).await
+    }
+    async fn passports(&self) -> Result<Vec<Passport>, Error> {
+        let mut connection =
+            self.pool.acquire().await.map_err(Error::storage)?;
+        let mut services: HashMap<String, Vec<String>> = HashMap::new();
+        let rows = sqlx::query(
+            "SELECT passport_id, service FROM passport_services ORDER BY \
+             passport_id, service",
+        )
+        .fetch_all(&mut *connection)
+        .await
+        .map_err(Error::storage)?;
+        for row in rows {
+            services
+                .entry(row.try_get("passport_id").map_err(Error::storage)?)
+                .or_default()
+                .push(row.try_get("service").map_err(Error::storage)?);
+        }
+        sqlx::query(
+            "SELECT id, password, account_type, disabled, confirmed, \
+             expires_at FROM passports ORDER BY id",
+        )
+        .fetch_all(&mut *connection)
+        .await
+        .map_err(Error::storage)?
+        .iter()
+        .map(|row| {
+            let id: String = row.try_get("id").map_err(Error::storage)?;
+            let services = services.remove(&id).unwrap_or_default();
+            passport_from_row(row, services)
+        })
+        .collect()
+    }
+    async fn set_passport(&self, passport: Passport) -> Result<String, Error> {
+        let mut transaction =
+            self.pool.begin().await.map_err(Error::storage)?;
+        store(&mut transaction, &passport).await?;
+        transaction.commit().await.map_err(Error::storage)?;
+        Ok(passport.id)
+    }
+    async fn remove_passport(
+        &self,
+        passport_id: &str,
+    ) -> Result<Option<Passport>, Error> {
+        let mut transaction =
+            self.pool.begin().await.map_err(Error::storage)?;
+        let passport = fetch(&mut transaction, passport_id).await?;
+        if passport.is_some() {
+            sqlx::query("DELETE FROM passport_services WHERE passport_id = $1")
+                .bind(passport_id)
+                .execute(&mut *transaction)
+                .await
+                .map_err(Error::storage)?;
+            sqlx::query("DELETE FROM passports WHERE id = $1")
+                .bind(passport_id)
+                .execute(&mut *transaction)
+                .await
+                .map_err(Error::storage)?;
+        }
+        transaction.commit().await.map_err(Error::storage)?;
+        Ok(passport)
+    }
+    /// Reads and stores the passport in a single transaction.
+    async fn update_passport(
+        &self,
+        passport_id: &str,
+        update: &mut (dyn FnMut(&mut Passport) -> Result<(), Error> + Send),
+    ) -> Result<Option<Passport>, Error> {
+        let mut transaction =
+            self.pool.begin().await.map_err(Error::storage)?;
+        let Some(mut passport) = fetch(&mut transaction, passport_id).await?
+        else {
+            return Ok(None);
+        };
+        update(&mut passport)?;
+        check_id(passport_id, &passport)?;
+        store(&mut transaction, &passport).await?;
+        transaction.commit().await.map_err(Error::storage)?;
+        Ok(Some(passport))
+    }
+    fn status_policy(&self) -> PassportStatusPolicy {
+        self.status_policy.clone()
+    }
+}
+
+/// Fairing that manages a [SqlitePassportRegister] using the pool of the
+/// [rocket_db_pools] [Database] `D`.
+///
+/// Runs the [migrations](MIGRATOR) at ignite, unless disabled. Ignite
+/// fairings run in the order they are attached, so attach this one after
+/// `D::init()`.
+pub struct PassportDatabase<D> {
+    migrate: bool,
+    status_policy: PassportStatusPolicy,
+    phantom_database: PhantomData<fn() -> D>,
+}
+
+impl<D> PassportDatabase<D>
+where
+    D: Database<Pool = SqlitePool>,
+{
+    /// Creates a new instance.
+    pub fn new() -> Self {
+        Self {
+            migrate: true,
+            status_policy: PassportStatusPolicy::default(),
+            phantom_database: PhantomData,
+        }
+    }
+
+    /// Sets whether the migrations are run at ignite.
+    pub fn with_migrations(mut self, migrate: bool) -> Self {
+        self.migrate = migrate;
+        self
+    }
+
+    /// Sets the [PassportStatusPolicy] of the managed register.
+    pub fn with_status_policy(
+        mut self,
+        status_policy: PassportStatusPolicy,
+    ) -> Self {
+        self.status_policy = status_policy;
+        self
+    }
+}
+
+impl<D> Default for PassportDatabase<D>
+where
+    D: Database<Pool = SqlitePool>,
+{
+    /// Default implementation:
+    ///
+    /// - Migrations: run at ignite
+    /// - Status policy: [PassportStatusPolicy::default]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[rocket::async_trait]
+impl<D> Fairing for PassportDatabase<D>
+where
+    D: Database<Pool = SqlitePool>,
+{
+    fn info(&self) -> Info {
+        Info {
+            name: "cosmodrome passport database",
+            kind: Kind::Ignite,
+        }
+    }
+
+    async fn on_ignite(&self, rocket: Rocket<Build>) -> fairing::Result {
+        let Some(database) = D::fetch(&rocket) else {
+            error!(
+                "No database {} is managed by rocket. Please attach its \
+                 initializer before the PassportDatabase fairing.",
+                D::NAME
+            );
+            return Err(rocket);
+        };
+        let register = SqlitePassportRegister::new((**database).clone())
+            .with_status_policy(self.status_policy.clone());
+        if self.migrate {
+            if let Err(e) = register.migrate().await {
+                error!("Could not migrate the passport database: {e}");
+                return Err(rocket);
+            }
+        }
+        Ok(rocket.manage(register))
+    }
+}
+
+/// Loads the passport with the given id.
+async fn fetch(
+    connection: &mut SqliteConnection,
+    passport_id: &str,
+) -> Result<Option<Passport>, Error> {
+    let Some(row) = sqlx::query(
+        "SELECT id, password, account_type, disabled, confirmed, expires_at \
+         FROM passports WHERE id = $1",
+    )
+    .bind(passport_id)
+    .fetch_optional(&mut *connection)
+    .await
+    .map_err(Error::storage)?
+    else {
+        return Ok(None);
+    };
+    let services = sqlx::query_scalar(
+        "SELECT service FROM passport_services WHERE passport_id = $1 ORDER \
+         BY service",
+    )
+    .bind(passport_id)
+    .fetch_all(&mut *connection)
+    .await
+    .map_err(Error::storage)?;
+    passport_from_row(&row, services).map(Some)
+}
+
+/// Inserts or replaces the given passport together with its services.
+async fn store(
+    connection: &mut SqliteConnection,
+    passport: &Passport,
+) -> Result<(), Error> {
+    sqlx::query(
+        "INSERT INTO passport_types (name) VALUES ($1) ON CONFLICT DO NOTHING",
+    )
+    .bind(type_name(&passport.account_type))
+    .execute(&mut *connection)
+    .await
+    .map_err(Error::storage)?;
+    sqlx::query(
+        "INSERT INTO passports (id, password, account_type, disabled, \
+         confirmed, expires_at) VALUES ($1, $2, $3, $4, $5, $6) ON CONFLICT \
+         (id) DO UPDATE SET password = excluded.password, account_type = \
+         excluded.account_type, disabled = excluded.disabled, confirmed = \
+         excluded.confirmed, expires_at = excluded.expires_at",
+    )
+    .bind(&passport.id)
+    .bind(passport.password_hash())
+    .bind(type_name(&passport.account_type))
+    .bind(passport.disabled)
+    .bind(passport.confirmed)
+    .bind(passport.expires_at)
+    .execute(&mut *connection)
+    .await
+    .map_err(Error::storage)?;
+    sqlx::query("DELETE FROM passport_services WHERE passport_id = $1")
+        .bind(&passport.id)
+        .execute(&mut *connection)
+        .await
+        .map_err(Error::storage)?;
+    for service in passport.services() {
+        sqlx::query(
+            "INSERT INTO passport_services (passport_id, service) VALUES ($1, \
+             $2) ON CONFLICT DO NOTHING",
+        )
+        .bind(&passport.id)
+        .bind(service)
+        .execute(&mut *connection)
+        .await
+        .map_err(Error::storage)?;
+    }
+    Ok(())
+}
+
+/// Creates a [Passport] from a row of the `passports` table.
+fn passport_from_row(
+    row: &SqliteRow,
+    services: Vec<String>,
+) -> Result<Passport, Error> {
+    let id: String = row.try_get("id").map_err(Error::storage)?;
+    let password: String = row.try_get("password").map_err(Error::storage)?;
+    let account_type: String =
+        row.try_get("account_type").map_err(Error::storage)?;
+    let expires_at: DateTime<Utc> =
+        row.try_get("expires_at").map_err(Error::storage)?;
+    Ok(Passport::from_parts(
+        &id,
+        &password,
+        services,
+        passport_type(account_type),
+        row.try_get("disabled").map_err(Error::storage)?,
+        row.try_get("confirmed").map_err(Error::storage)?,
+        expires_at,
+    ))
+}
+
+/// The prefix of custom [PassportType]s in the database.
+const CUSTOM_TYPE_PREFIX: &str = "custom:";
+
+/// Returns the name of the given [PassportType] as stored in the database.
+fn type_name(passport_type: &PassportType) -> String {
+    match passport_type {
+        PassportType::Admin => "Admin".to_string(),
+        PassportType::Moderator => "Moderator".to_string(),
+        PassportType::User => "User".to_string(),
+        PassportType::Custom(name) => format!("{CUSTOM_TYPE_PREFIX}{name}"),
+    }
+}
+
+/// Returns the [PassportType] for the given name.
+fn passport_type(name: String) -> PassportType {
+    if let Some(custom) = name.strip_prefix(CUSTOM_TYPE_PREFIX) {
+        return PassportType::Custom(custom.to_string());
+    }
+    match name.as_str() {
+        "Admin" => PassportType::Admin,
+        "Moderator" => PassportType::Moderator,
+        "User" => PassportType::User,
+        _ => PassportType::Custom(name),
+    }
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+    use super::*;
+    use crate::Ticket;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    /// Creates a register backed by a migrated in-memory database. A single
+    /// connection is used, as every connection opens its own database.
+    async fn register() -> SqlitePassportRegister {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        MIGRATOR.run(&pool).await.unwrap();
+        SqlitePassportRegister::new(pool)
+    }
+
+    fn passport(
+        id: &str,
+        services: &[&str],
+        account_type: PassportType,
+    ) -> Passport {
+        Passport::new(id, "secret", services, account_type).unwrap()
+    }
+
+    #[rocket::async_test]
+    async fn set_passport_inserts_and_replaces() {
+        let register = register().await;
+        assert!(register.passport("cosmo").await.unwrap().is_none());
+
+        let mut cosmo = passport("cosmo", &["a", "b"], PassportType::User);
+        cosmo.confirmed = true;
+        let expires_at = cosmo.expires_at;
+        assert_eq!(register.set_passport(cosmo).await.unwrap(), "cosmo");
+        let stored = register.passport("cosmo").await.unwrap().unwrap();
+        assert_eq!(stored.services(), ["a", "b"]);
+        assert_eq!(stored.account_type, PassportType::User);
+        assert!(stored.confirmed);
+        assert!(!stored.disabled);
+        assert_eq!(stored.expires_at, expires_at);
+
+        let replacement = passport("cosmo", &["c"], PassportType::Admin);
+        register.set_passport(replacement).await.unwrap();
+        register
+            .set_passport(passport("naut", &[], PassportType::User))
+            .await
+            .unwrap();
+        let passports = register.passports().await.unwrap();
+        let ids: Vec<_> = passports.iter().map(|p| p.id.as_str()).collect();
+        assert_eq!(ids, ["cosmo", "naut"]);
+        assert_eq!(passports[0].services(), ["c"]);
+        assert_eq!(passports[0].account_type, PassportType::Admin);
+        assert!(!passports[0].confirmed);
+        assert!(passports[1].services().is_empty());
+    }
+
+    #[rocket::async_test]
+    async fn custom_passport_type_round_trips() {
+        let register = register().await;
+        let pilot = PassportType::Custom("Pilot".to_string());
+        register
+            .set_passport(passport("cosmo", &[], pilot.clone()))
+            .await
+            .unwrap();
+        register
+            .set_passport(passport("naut", &[], pilot.clone()))
+            .await
+            .unwrap();
+        for passport in register.passports().await.unwrap() {
+            assert_eq!(passport.account_type, pilot);
+        }
+    }
+
+    #[rocket::async_test]
+    async fn custom_passport_type_is_not_loaded_as_built_in() {
+        let register = register().await;
+        let custom = [
+            PassportType::Custom("Admin".to_string()),
+            PassportType::Custom("custom:User".to_string()),
+        ];
+        for (i, account_type) in custom.iter().enumerate() {
+            register
+                .set_passport(passport(
+                    &i.to_string(),
+                    &[],
+                    account_type.clone(),
+                ))
+                .await
+                .unwrap();
+        }
+        register
+            .set_passport(passport("admin", &[], PassportType::Admin))
+            .await
+            .unwrap();
+        for (i, account_type) in custom.iter().enumerate() {
+            let passport =
+                register.passport(&i.to_string()).await.unwrap().unwrap();
+            assert_eq!(&passport.account_type, account_type);
+        }
+        let passport = register.passport("admin").await.unwrap().unwrap();
+        assert_eq!(passport.account_type, PassportType::Admin);
+    }
+
+    #[rocket::async_test]
+    async fn remove_passport_deletes_passport_and_services() {
+        let register = register().await;
+        register
+            .set_passport(passport("cosmo", &["a"], PassportType::User))
+            .await
+            .unwrap();
+        let removed = register.remove_passport("cosmo").await.unwrap();
+        assert_eq!(removed.unwrap().services(), ["a"]);
+        assert!(register.remove_passport("cosmo").await.unwrap().is_none());
+        assert!(register.passport("cosmo").await.unwrap().is_none());
+        let services: i64 =
+            sqlx::query_scalar("SELECT COUNT(*) FROM passport_services")
+                .fetch_one(register.pool())
+                .await
+                .unwrap();
+        assert_eq!(services, 0);
+    }
+
+    #[rocket::async_test]
+    async fn update_passport_stores_changes() {
+        let register = register().await;
+        register
+            .set_passport(passport("cosmo", &["a"], PassportType::User))
+            .await
+            .unwrap();
+        let updated = register
+            .update_passport("cosmo", &mut |p| {
+                p.disabled = true;
+                p.set_services(&["b", "c"]);
+                Ok(())
+            })
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(updated.disabled);
+        let stored = register.passport("cosmo").await.unwrap().unwrap();
+        assert!(stored.disabled);
+        assert_eq!(stored.services(), ["b", "c"]);
+
+        let missing = register
+            .update_passport("naut", &mut |_| Ok(()))
+            .await
+            .unwrap();
+        assert!(missing.is_none());
+    }
+
+    #[rocket::async_test]
+    async fn update_passport_rolls_back_on_error() {
+        let register = register().await;
+        register
+            .set_passport(passport("cosmo", &["a"], PassportType::User))
+            .await
+            .unwrap();
+        let result = register
+            .update_passport("cosmo", &mut |p| {
+                p.id = "naut".to_string();
+                p.set_services(&["b"]);
+                Ok(())
+            })
+            .await;
+        assert!(matches!(result, Err(Error::Configuration(_))));
+        let result = register
+            .update_passport("cosmo", &mut |p| {
+                p.disabled = true;
+                Err(Error::Forbidden("Not allowed.".to_string()))
+            })
+            .await;
+        assert!(matches!(result, Err(Error::Forbidden(_))));
+
+        let passports = register.passports().await.unwrap();
+        assert_eq!(passports.len(), 1);
+        assert_eq!(passports[0].id, "cosmo");
+        assert_eq!(passports[0].services(), ["a"]);
+        assert!(!passports[0].disabled);
+    }
+
+    #[rocket::async_test]
+    async fn verify_credentials() {
+        let register = register().await;
+        register
+            .set_passport(passport("cosmo", &[], PassportType::User))
+            .await
+            .unwrap();
+        let valid = register
+            .verify_credentials(&Ticket::new("cosmo", "secret"))
+            .await
+            .unwrap();
+        assert_eq!(valid.unwrap().id, "cosmo");
+        let wrong = register
+            .verify_credentials(&Ticket::new("cosmo", "wrong"))
+            .await;
+        assert!(matches!(wrong, Err(Error::InvalidCredentials)));
+        let unknown = register
+            .verify_credentials(&Ticket::new("naut", "secret"))
+            .await;
+        assert!(matches!(unknown, Ok(None)));
+    }
+}
@@ -4,6 +4,7 @@ use super::{
         AuthType,
         Bearer,
         Cookie,
+        Session,
     },
     boarding_pass::{
         payloads::JsonWebToken,
@@ -11,10 +12,15 @@ use super::{
     },
     ciphering::{
         Ciphering,
-        JwtCipher,
+        Issuing,
+    },
+    error::Error,
+    passport::Passport,
+    session::{
+        SessionBackend,
+        SessionStore,
     },
 };
-use anyhow::anyhow;
 use rocket::http::{
     Cookie as RocketCookie,
     CookieJar,
@@ -31,14 +37,19 @@ where
     fn boarding_pass(
         &self,
         identifier: ID,
-    ) -> anyhow::Result<Option<BoardingPass<BPD, AT>>>;
+    ) -> Result<Option<BoardingPass<BPD, AT>>, Error>;
     /// Stores the given [BoardingPass] in your storage.
     fn store_boarding_pass(
         &self,
         boarding_pass: &BoardingPass<BPD, AT>,
-    ) -> anyhow::Result<ENC>;
+    ) -> Result<ENC, Error>;
     /// Removes the [BoardingPass] from your storage.
-    fn remove_boarding_pass(&self, identifier: ID) -> anyhow::Result<()>;
+    fn remove_boarding_pass(&self, identifier: ID) -> Result<(), Error>;
+    /// Issues a new [BoardingPass] for the given [Passport].
+    fn issue_boarding_pass(
+        &self,
+        passport: &Passport,
+    ) -> Result<BoardingPass<BPD, AT>, Error>;
 }
 
 /*
@@ -77,7 +88,7 @@ impl<'a> Default for CookieStorageOptions<'a> {
     /// - Path: `/`
     /// - Secure: `true`
     /// - Same site: [SameSite::Strict](rocket::http::SameSite::Strict)
-    /// - Expires: `1 week`
+    /// - Expires: follows the `exp` claim of the stored [BoardingPass]
     fn default() -> Self {
         Self {
             cookie_template: RocketCookie::build((
@@ -87,11 +98,6 @@ impl<'a> Default for CookieStorageOptions<'a> {
             .path("/")
             .secure(true)
             .same_site(rocket::http::SameSite::Strict)
-            .expires({
-                let one_week = time::OffsetDateTime::now_utc()
-                    + core::time::Duration::from_secs(604800);
-                cookie::Expiration::from(one_week)
-            })
             .build(),
         }
     }
@@ -136,22 +142,24 @@ where
     }
 }
 
-impl BoardingPassStorage<JsonWebToken, Cookie, (), String>
+impl<C> BoardingPassStorage<JsonWebToken, Cookie, (), String>
     for Storage<
         &CookieJar<'_>,
         CookieStorageOptions<'static>,
         JsonWebToken,
         Cookie,
-        JwtCipher,
+        C,
         String,
     >
+where
+    C: Ciphering<JsonWebToken, Cookie, String> + Issuing<JsonWebToken, Cookie>,
 {
     /// In the case of usage with [Cookie](RocketCookie), the identifier is not used. Instead, the
     /// given name of the [cookie_template](CookieStorageOptions::cookie_template) is used.
     fn boarding_pass(
         &self,
         _identifier: (),
-    ) -> anyhow::Result<Option<BoardingPass<JsonWebToken, Cookie>>> {
+    ) -> Result<Option<BoardingPass<JsonWebToken, Cookie>>, Error> {
         let Some(boarding_pass) = self
             .storage
             .get_private(self.options.cookie_template.name())
@@ -165,19 +173,18 @@ impl BoardingPassStorage<JsonWebToken, Cookie, (), String>
     fn store_boarding_pass(
         &self,
         boarding_pass: &BoardingPass<JsonWebToken, Cookie>,
-    ) -> anyhow::Result<String> {
-        let token = self
-            .cipher
-            .encode(boarding_pass)
-            .map_err(|e| anyhow!("{e}"))?;
+    ) -> Result<String, Error> {
+        let token = self.cipher.encode(boarding_pass)?;
         let mut cookie = self.options.cookie_template.clone();
         cookie.set_value(token.clone());
+        cookie.set_max_age(None);
+        cookie.set_expires(expires(boarding_pass)?);
         self.storage.add_private(cookie);
         Ok(token)
     }
     /// In the case of usage with [Cookie](RocketCookie), the identifier is not used. Instead, the
     /// given name of the [cookie_template](CookieStorageOptions::cookie_template) is used.
-    fn remove_boarding_pass(&self, _identifier: ()) -> anyhow::Result<()> {
+    fn remove_boarding_pass(&self, _identifier: ()) -> Result<(), Error> {
         let cookie = RocketCookie::build(
             self.options.cookie_template.name().to_string(),
         );
@@ -188,31 +195,115 @@ impl BoardingPassStorage<JsonWebToken, Cookie, (), String>
         self.storage.remove_private(cookie);
         Ok(())
     }
+    fn issue_boarding_pass(
+        &self,
+        passport: &Passport,
+    ) -> Result<BoardingPass<JsonWebToken, Cookie>, Error> {
+        Ok(self.cipher.issue(passport))
+    }
 }
 
-impl<'r> BoardingPassStorage<JsonWebToken, Bearer, (), String>
-    for Storage<(), (), JsonWebToken, Bearer, JwtCipher, String>
+impl<C> BoardingPassStorage<JsonWebToken, Bearer, (), String>
+    for Storage<(), (), JsonWebToken, Bearer, C, String>
+where
+    C: Ciphering<JsonWebToken, Bearer, String> + Issuing<JsonWebToken, Bearer>,
 {
     /// The [BoardingPass] is extracted from the [AUTHORIZATION](http::header::AUTHORIZATION) header.
     fn boarding_pass(
         &self,
         _identifier: (),
-    ) -> anyhow::Result<Option<BoardingPass<JsonWebToken, Bearer>>> {
+    ) -> Result<Option<BoardingPass<JsonWebToken, Bearer>>, Error> {
         Ok(None)
     }
     fn store_boarding_pass(
         &self,
         boarding_pass: &BoardingPass<JsonWebToken, Bearer>,
-    ) -> anyhow::Result<String> {
-        let token = self
-            .cipher
-            .encode(boarding_pass)
-            .map_err(|e| anyhow!("{e}"))?;
+    ) -> Result<String, Error> {
+        let token = self.cipher.encode(boarding_pass)?;
         Ok(token)
     }
     /// In the case of usage with [Cookie](RocketCookie), the identifier is not used. Instead, the
     /// given name of the [cookie_template](CookieStorageOptions::cookie_template) is used.
-    fn remove_boarding_pass(&self, _identifier: ()) -> anyhow::Result<()> {
+    fn remove_boarding_pass(&self, _identifier: ()) -> Result<(), Error> {
         Ok(())
     }
+    fn issue_boarding_pass(
+        &self,
+        passport: &Passport,
+    ) -> Result<BoardingPass<JsonWebToken, Bearer>, Error> {
+        Ok(self.cipher.issue(passport))
+    }
+}
+
+impl<C> BoardingPassStorage<JsonWebToken, Session, String, String>
+    for Storage<
+        (&CookieJar<'_>, &SessionStore),
+        CookieStorageOptions<'static>,
+        JsonWebToken,
+        Session,
+        C,
+        String,
+    >
+where
+    C: Ciphering<JsonWebToken, Session, String>
+        + Issuing<JsonWebToken, Session>,
+{
+    /// The identifier is the session id, which equals the
+    /// [jti](JsonWebToken::jti) of the [BoardingPass].
+    fn boarding_pass(
+        &self,
+        identifier: String,
+    ) -> Result<Option<BoardingPass<JsonWebToken, Session>>, Error> {
+        let (_, sessions) = self.storage;
+        Ok(sessions.session(&identifier)?.map(BoardingPass::new))
+    }
+    /// Stores the [BoardingPass] in the [SessionStore] and its session id in
+    /// the cookie. Returns the session id.
+    fn store_boarding_pass(
+        &self,
+        boarding_pass: &BoardingPass<JsonWebToken, Session>,
+    ) -> Result<String, Error> {
+        let (cookies, sessions) = self.storage;
+        let session_id = boarding_pass.data.jti.clone();
+        sessions.store_session(&session_id, boarding_pass.data.clone())?;
+        let mut cookie = self.options.cookie_template.clone();
+        cookie.set_value(session_id.clone());
+        cookie.set_max_age(None);
+        cookie.set_expires(expires(boarding_pass)?);
+        cookies.add_private(cookie);
+        Ok(session_id)
+    }
+    /// Removes the session from the [SessionStore]. The cookie is only
+    /// removed if it contains the given session id.
+    fn remove_boarding_pass(&self, identifier: String) -> Result<(), Error> {
+        let (cookies, sessions) = self.storage;
+        sessions.remove_session(&identifier)?;
+        let name = self.options.cookie_template.name();
+        if cookies
+            .get_private(name)
+            .is_some_and(|c| c.value() == identifier)
+        {
+            let cookie = RocketCookie::build(name.to_string());
+            let cookie = match self.options.cookie_template.path() {
+                Some(path) => cookie.path(path.to_string()),
+                None => cookie,
+            };
+            cookies.remove_private(cookie);
+        }
+        Ok(())
+    }
+    fn issue_boarding_pass(
+        &self,
+        passport: &Passport,
+    ) -> Result<BoardingPass<JsonWebToken, Session>, Error> {
+        Ok(self.cipher.issue(passport))
+    }
+}
+
+/// Returns the expiry of the cookie that stores the given [BoardingPass].
+fn expires<AT: AuthType>(
+    boarding_pass: &BoardingPass<JsonWebToken, AT>,
+) -> Result<time::OffsetDateTime, Error> {
+    time::OffsetDateTime::from_unix_timestamp(boarding_pass.data.exp() as i64)
+        .map_err(|e| Error::InvalidToken(e.to_string()))
 }
@@ -0,0 +1,119 @@
+//! A [Traveler] is the holder of a valid [BoardingPass] together with the
+//! corresponding [Passport] from the
+//! [PassportRegister](super::passport_register::PassportRegister).
+use super::{
+    asynchronous::AsyncPassportRegister,
+    auth_type::AuthType,
+    boarding_pass::{
+        payloads::JsonWebToken,
+        BoardingPass,
+    },
+    error::Error,
+    passport::Passport,
+    passport_register::MemoryPassportRegister,
+    problem::fail,
+};
+use log::error;
+use rocket::{
+    http::Status,
+    request::{
+        FromRequest,
+        Outcome,
+        Request,
+    },
+    Ignite,
+    Rocket,
+    Sentinel,
+};
+use std::marker::PhantomData;
+
+/// Request guard that rehydrates the [Passport] referenced by the `sub` claim
+/// of a [BoardingPass].
+///
+/// The [Passport] is checked against the
+/// [status_policy](AsyncPassportRegister::status_policy) of the register on every
+/// request, so disabling it takes effect immediately. Use the [BoardingPass]
+/// guard if this is not required.
+///
+/// Requires an instance of `PR` in [rocket]s global state.
+#[derive(Debug)]
+pub struct Traveler<AT: AuthType, PR = MemoryPassportRegister> {
+    /// The [BoardingPass] that has been presented.
+    pub boarding_pass: BoardingPass<JsonWebToken, AT>,
+    /// The [Passport] of the traveler, loaded from the register.
+    pub passport: Passport,
+    phantom_register: PhantomData<PR>,
+}
+
+/// Aborts launch if a route requires a [Traveler] while the [BoardingPass] or
+/// `PR` would not be available.
+impl<AT, PR> Sentinel for Traveler<AT, PR>
+where
+    AT: AuthType,
+    BoardingPass<JsonWebToken, AT>: Sentinel,
+    PR: AsyncPassportRegister + 'static,
+{
+    fn abort(rocket: &Rocket<Ignite>) -> bool {
+        if BoardingPass::<JsonWebToken, AT>::abort(rocket) {
+            return true;
+        }
+        if rocket.state::<PR>().is_none() {
+            error!(
+                "A route requires a Traveler, but no cosmodrome \
+                 PassportRegister of type {} is managed by rocket.",
+                std::any::type_name::<PR>()
+            );
+            return true;
+        }
+        false
+    }
+}
+
+#[rocket::async_trait]
+impl<'r, AT, PR> FromRequest<'r> for Traveler<AT, PR>
+where
+    AT: AuthType + Send + Sync,
+    BoardingPass<JsonWebToken, AT>: FromRequest<'r, Error = Error>,
+    PR: AsyncPassportRegister + 'static,
+{
+    type Error = Error;
+
+    async fn from_request(
+        request: &'r Request<'_>,
+    ) -> Outcome<Self, Self::Error> {
+        let boarding_pass =
+            match request.guard::<BoardingPass<JsonWebToken, AT>>().await {
+                Outcome::Success(b) => b,
+                Outcome::Error(e) => return Outcome::Error(e),
+                Outcome::Forward(s) => return Outcome::Forward(s),
+            };
+        let Some(register) = request.rocket().state::<PR>() else {
+            error!(
+                "No cosmodrome PassportRegister managed by rocket. Please \
+                 create an instance and manage it with rocket."
+            );
+            return Outcome::Forward(Status::InternalServerError);
+        };
+        let passport = match register.passport(&boarding_pass.data.sub).await {
+            Err(e) => {
+                error!("{e}");
+                return Outcome::Forward(Status::InternalServerError);
+            }
+            Ok(p) => p,
+        };
+        let Some(passport) = passport else {
+            return fail(
+                request,
+                Error::PassportNotFound(boarding_pass.data.sub),
+            );
+        };
+        match register.status_policy().check(&passport) {
+            Err(e) => fail(request, e.into()),
+            Ok(()) => Outcome::Success(Self {
+                boarding_pass,
+                passport,
+                phantom_register: PhantomData,
+            }),
+        }
+    }
+}